@@ -0,0 +1,278 @@
+use rocket::form::{Form, FromForm};
+use rocket::request::{self, FlashMessage, FromRequest};
+use rocket::response::{Flash, Redirect};
+use rocket::serde::Serialize;
+use rocket::{get, post, routes, uri, Orbit, Request, Rocket, Route, State};
+use rocket_dyn_templates::{context, Template};
+use tokio::task;
+use ulid::Ulid;
+
+use crate::auth::{scope, Authorized};
+use crate::day::{DayInfo, Days};
+#[cfg(feature = "day13")]
+use crate::day13::Day13;
+#[cfg(feature = "day18")]
+use crate::day18::Day18;
+#[cfg(feature = "day19")]
+use crate::day19::Day19;
+use crate::error::ApiError;
+use crate::health;
+#[cfg(feature = "database")]
+use crate::infrastructure::with_pool;
+use crate::infrastructure::Infrastructure;
+#[cfg(feature = "reindeer")]
+use crate::reindeer::ReindeerRegistry;
+use crate::security::WithPolicy;
+
+/// Allows the dashboard its inline styles and the reset forms, nothing else.
+const POLICY: &str = "default-src 'none'; style-src 'unsafe-inline'; form-action 'self'; \
+    base-uri 'none'; frame-ancestors 'none'";
+
+/// Token the reset forms have to carry, since browsers send basic credentials along with forms
+/// posted from any other site.
+pub struct CsrfToken(String);
+
+impl Default for CsrfToken {
+    fn default() -> Self {
+        CsrfToken(Ulid::new().to_string())
+    }
+}
+
+/// The running rocket, to look up the state of days that may not be mounted.
+struct Running<'r>(&'r Rocket<Orbit>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Running<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(Running(request.rocket()))
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Room {
+    room: i32,
+    clients: usize,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct BirdApp {
+    views: u64,
+    rooms: Vec<Room>,
+}
+
+/// Rows in a table, or why they could not be counted.
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Table {
+    name: &'static str,
+    rows: Option<i64>,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Persisted {
+    keys: Vec<String>,
+    error: Option<String>,
+}
+
+/// A day whose state the dashboard can reset.
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Reset {
+    day: &'static str,
+    description: &'static str,
+}
+
+fn resets() -> Vec<Reset> {
+    vec![
+        #[cfg(feature = "day13")]
+        Reset {
+            day: "day13",
+            description: "Recreate the orders table",
+        },
+        #[cfg(feature = "day18")]
+        Reset {
+            day: "day18",
+            description: "Recreate the regions and orders tables",
+        },
+        #[cfg(feature = "day19")]
+        Reset {
+            day: "day19",
+            description: "Reset the bird app view counter",
+        },
+        #[cfg(feature = "reindeer")]
+        Reset {
+            day: "reindeer",
+            description: "Drop the reindeer roster and contest history",
+        },
+    ]
+}
+
+#[cfg(feature = "day19")]
+fn bird_app(rocket: &Rocket<Orbit>) -> Option<BirdApp> {
+    rocket.state::<Day19>().map(|day19| BirdApp {
+        views: day19.views(),
+        rooms: day19
+            .clients()
+            .into_iter()
+            .map(|(room, clients)| Room { room, clients })
+            .collect(),
+    })
+}
+
+#[cfg(not(feature = "day19"))]
+fn bird_app(_rocket: &Rocket<Orbit>) -> Option<BirdApp> {
+    None
+}
+
+#[cfg(feature = "database")]
+async fn tables(infrastructure: &Infrastructure) -> Vec<Table> {
+    let mut tables = Vec::new();
+    let names = [
+        // Day 18 recreates the orders table of day 13 along with its regions
+        #[cfg(any(feature = "day13", feature = "day18"))]
+        "orders",
+        #[cfg(feature = "day18")]
+        "regions",
+        #[cfg(feature = "reindeer")]
+        "reindeer",
+        #[cfg(feature = "reindeer")]
+        "contest_results",
+    ];
+    for name in names {
+        // Table names cannot be bound, these are the ones of days 13 and 18 and the registry
+        let sql = format!("SELECT COUNT(*) FROM {}", name);
+        let counted = with_pool!(&infrastructure.database, |pool| {
+            sqlx::query_scalar::<_, i64>(&sql).fetch_one(pool).await
+        });
+        tables.push(match counted {
+            Ok(rows) => Table {
+                name,
+                rows: Some(rows),
+                error: None,
+            },
+            Err(e) => Table {
+                name,
+                rows: None,
+                error: Some(e.to_string()),
+            },
+        });
+    }
+    tables
+}
+
+#[cfg(not(feature = "database"))]
+async fn tables(_infrastructure: &Infrastructure) -> Vec<Table> {
+    Vec::new()
+}
+
+async fn persisted(infrastructure: &Infrastructure) -> Persisted {
+    let persist = infrastructure.persist.clone();
+    // Off the async workers, as the readiness check does for the same directory
+    let keys = task::spawn_blocking(move || persist.list().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|keys| keys);
+    match keys {
+        Ok(keys) => Persisted {
+            keys: keys
+                .into_iter()
+                .filter(|key| !key.starts_with(health::PERSIST_PREFIX))
+                .collect(),
+            error: None,
+        },
+        Err(e) => Persisted {
+            keys: Vec::new(),
+            error: Some(e),
+        },
+    }
+}
+
+#[get("/admin")]
+async fn dashboard(
+    _authorized: Authorized<scope::Admin>,
+    running: Running<'_>,
+    days: &State<Days>,
+    infrastructure: &State<Infrastructure>,
+    csrf: &State<CsrfToken>,
+    flash: Option<FlashMessage<'_>>,
+) -> WithPolicy<Template> {
+    let days: &[DayInfo] = &days.0;
+    WithPolicy(
+        POLICY,
+        Template::render(
+            "admin",
+            context! {
+                days,
+                bird_app: bird_app(running.0),
+                tables: tables(infrastructure).await,
+                persisted: persisted(infrastructure).await,
+                resets: resets(),
+                csrf: &csrf.0,
+                flash: flash.map(|flash| context! {
+                    kind: flash.kind().to_string(),
+                    message: flash.message().to_string(),
+                }),
+            },
+        ),
+    )
+}
+
+#[derive(FromForm)]
+struct ResetForm<'r> {
+    csrf: &'r str,
+}
+
+#[post("/admin/reset/<day>", data = "<form>")]
+async fn reset(
+    _authorized: Authorized<scope::Admin>,
+    day: &str,
+    form: Form<ResetForm<'_>>,
+    csrf: &State<CsrfToken>,
+    #[cfg_attr(not(feature = "day19"), allow(unused_variables))] running: Running<'_>,
+    #[cfg_attr(
+        not(any(feature = "day13", feature = "day18", feature = "reindeer")),
+        allow(unused_variables)
+    )]
+    infrastructure: &State<Infrastructure>,
+) -> Result<Flash<Redirect>, ApiError> {
+    if form.csrf != csrf.0 {
+        return Err(ApiError::Forbidden(
+            "The form was not posted from the dashboard".to_string(),
+        ));
+    }
+    let reset: Option<Result<(), ApiError>> = match day {
+        #[cfg(feature = "day13")]
+        "day13" => Some(Day13::reset(&infrastructure.database).await),
+        #[cfg(feature = "day18")]
+        "day18" => Some(Day18::reset(&infrastructure.database).await),
+        #[cfg(feature = "day19")]
+        "day19" => Some(match running.0.state::<Day19>() {
+            Some(day19) => {
+                day19.reset();
+                Ok(())
+            }
+            None => Err(ApiError::NotFound("day19 is not mounted".to_string())),
+        }),
+        #[cfg(feature = "reindeer")]
+        "reindeer" => Some(ReindeerRegistry::reset(&infrastructure.database).await),
+        _ => None,
+    };
+    let Some(reset) = reset else {
+        return Err(ApiError::NotFound(format!("{} has nothing to reset", day)));
+    };
+    let dashboard = Redirect::to(uri!(dashboard));
+    Ok(match reset {
+        Ok(()) => Flash::success(dashboard, format!("Reset {}", day)),
+        Err(e) => Flash::error(dashboard, format!("Could not reset {}: {}", day, e)),
+    })
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![dashboard, reset]
+}
@@ -0,0 +1,256 @@
+use std::fmt::{self, Display, Formatter};
+#[cfg(feature = "auth")]
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+#[cfg(feature = "admin")]
+use base64::Engine;
+
+use rocket::fairing::{AdHoc, Fairing};
+#[cfg(feature = "auth")]
+use rocket::request::{self, FromRequest};
+use rocket::Request;
+use sha2::{Digest, Sha256};
+use tracing::{error, warn};
+
+#[cfg(feature = "auth")]
+use crate::error::ApiError;
+use crate::infrastructure::Infrastructure;
+
+/// What an API key may do, each scope including the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Scope {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(format!("Unknown scope {}", s)),
+        }
+    }
+}
+
+pub type Hash = [u8; 32];
+
+/// The configured API keys, of which only the SHA-256 hashes are kept.
+pub struct ApiKeys(Vec<(Hash, Scope)>);
+
+impl ApiKeys {
+    /// Secret with the comma separated `scope:key` entries, where `scope:sha256:<hex>` gives the
+    /// key by its hash instead.
+    const SECRET: &'static str = "API_KEYS";
+
+    fn hash(key: &str) -> Hash {
+        Sha256::digest(key.as_bytes()).into()
+    }
+
+    fn parse_hex(hex: &str) -> Option<Hash> {
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+        let mut hash = [0; 32];
+        for (byte, digits) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+        }
+        Some(hash)
+    }
+
+    fn parse(entries: &str) -> Result<Self, String> {
+        entries
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .enumerate()
+            .map(|(index, entry)| {
+                let (scope, key) = entry
+                    .split_once(':')
+                    .ok_or_else(|| format!("Entry {} is not of the form scope:key", index))?;
+                let scope = scope.parse::<Scope>()?;
+                let hash = match key.strip_prefix("sha256:") {
+                    Some(hex) => Self::parse_hex(hex).ok_or_else(|| {
+                        format!("Entry {} is not a hex encoded SHA-256 hash", index)
+                    })?,
+                    None if key.is_empty() => return Err(format!("Entry {} has no key", index)),
+                    None => Self::hash(key),
+                };
+                Ok((hash, scope))
+            })
+            .collect::<Result<_, _>>()
+            .map(ApiKeys)
+    }
+
+    /// The hash of `key`, if it is one of the configured keys.
+    pub fn known(&self, key: &str) -> Option<Hash> {
+        let hash = Self::hash(key);
+        self.0
+            .iter()
+            .any(|(known, _)| *known == hash)
+            .then_some(hash)
+    }
+
+    #[cfg(feature = "auth")]
+    fn scope(&self, key: &str) -> Option<Scope> {
+        let hash = Self::hash(key);
+        self.0
+            .iter()
+            .filter(|(known, _)| *known == hash)
+            .map(|(_, scope)| *scope)
+            .max()
+    }
+
+    /// Reads the keys from the secrets, refusing to launch if any of them is malformed.
+    pub fn fairing() -> impl Fairing {
+        AdHoc::try_on_ignite("API keys", |rocket| async {
+            let entries = rocket
+                .state::<Infrastructure>()
+                .and_then(|infrastructure| infrastructure.secrets.get(Self::SECRET));
+            let Some(entries) = entries else {
+                warn!(
+                    "No {} secret, the routes that require an API key refuse every request",
+                    Self::SECRET
+                );
+                return Ok(rocket.manage(ApiKeys(Vec::new())));
+            };
+            match Self::parse(entries) {
+                Ok(keys) => Ok(rocket.manage(keys)),
+                Err(e) => {
+                    error!("Invalid {} secret: {}", Self::SECRET, e);
+                    Err(rocket)
+                }
+            }
+        })
+    }
+}
+
+/// The scope an [`Authorized`] guard requires.
+#[cfg(feature = "auth")]
+pub trait Required: Send + Sync + 'static {
+    const SCOPE: Scope;
+}
+
+/// Markers for [`Authorized`], one per [`Scope`] that routes require, each compiled with the days
+/// that require it.
+#[cfg(feature = "auth")]
+pub mod scope {
+    use super::{Required, Scope};
+
+    #[cfg(feature = "write-scope")]
+    pub struct Write;
+    #[cfg(feature = "admin-scope")]
+    pub struct Admin;
+
+    #[cfg(feature = "write-scope")]
+    impl Required for Write {
+        const SCOPE: Scope = Scope::Write;
+    }
+
+    #[cfg(feature = "admin-scope")]
+    impl Required for Admin {
+        const SCOPE: Scope = Scope::Admin;
+    }
+}
+
+/// Guards a route with an API key of at least scope `S`, sent as `Authorization: Bearer <key>` or
+/// in the `X-Api-Key` header.
+///
+/// Fails with 401 if the key is missing or unknown, and with 403 if its scope is too narrow.
+#[cfg(feature = "auth")]
+pub struct Authorized<S>(PhantomData<S>);
+
+/// How 401 responses ask for an API key.
+#[cfg(not(feature = "admin"))]
+pub const CHALLENGE: &str = "Bearer";
+/// How 401 responses ask for an API key, offering browsers to enter it as a password.
+#[cfg(feature = "admin")]
+pub const CHALLENGE: &str = "Bearer, Basic realm=\"cch23\"";
+
+/// The API key the request was sent with, known or not.
+pub fn presented_key<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    let authorization = request.headers().get_one("Authorization");
+    let key = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| request.headers().get_one("X-Api-Key"))
+        .map(str::trim);
+    #[cfg(feature = "admin")]
+    let key = key.or_else(|| {
+        authorization
+            .and_then(|value| value.strip_prefix("Basic "))
+            .and_then(|credentials| basic_password(request, credentials.trim()))
+    });
+    key
+}
+
+/// Decoded once per request, so the key can be borrowed from the request like the others.
+#[cfg(feature = "admin")]
+struct BasicPassword(String);
+
+/// The password of basic credentials, which is how browsers send the key to the admin dashboard.
+#[cfg(feature = "admin")]
+fn basic_password<'r>(request: &'r Request<'_>, credentials: &str) -> Option<&'r str> {
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(credentials)
+        .ok()
+        .and_then(|decoded| String::from_utf8(decoded).ok())?;
+    let (_user, password) = decoded.split_once(':')?;
+    let password = BasicPassword(password.to_string());
+    Some(request.local_cache(|| password).0.as_str())
+}
+
+#[cfg(feature = "auth")]
+#[rocket::async_trait]
+impl<'r, S: Required> FromRequest<'r> for Authorized<S> {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let Some(key) = presented_key(request) else {
+            return request::Outcome::Error(
+                ApiError::Unauthorized(
+                    "Missing API key, send it as a bearer token or in X-Api-Key".to_string(),
+                )
+                .reject(request),
+            );
+        };
+        let scope = request
+            .rocket()
+            .state::<ApiKeys>()
+            .and_then(|keys| keys.scope(key));
+        match scope {
+            None => request::Outcome::Error(
+                ApiError::Unauthorized("Unknown API key".to_string()).reject(request),
+            ),
+            Some(scope) if scope < S::SCOPE => request::Outcome::Error(
+                ApiError::Forbidden(format!(
+                    "API key has the {} scope, this route requires {}",
+                    scope,
+                    S::SCOPE
+                ))
+                .reject(request),
+            ),
+            Some(_) => request::Outcome::Success(Authorized(PhantomData)),
+        }
+    }
+}
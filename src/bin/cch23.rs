@@ -0,0 +1,25 @@
+use std::error::Error;
+use std::process::ExitCode;
+
+use cch23_santa4586::infrastructure::Infrastructure;
+use cch23_santa4586::{cli, telemetry};
+use rocket::Config;
+
+/// Runs the server without Shuttle, using the database and persist directory from the Rocket config.
+///
+/// With arguments, runs a single solver instead, see [`cli::run`].
+fn main() -> Result<ExitCode, Box<dyn Error>> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        return Ok(cli::run(&args));
+    }
+    rocket::execute(serve())?;
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn serve() -> Result<(), Box<dyn Error>> {
+    telemetry::init();
+    let infrastructure = Infrastructure::from_figment(&Config::figment()).await?;
+    cch23_santa4586::build(infrastructure).launch().await?;
+    Ok(())
+}
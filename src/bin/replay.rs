@@ -0,0 +1,49 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use cch23_santa4586::record::{Replay, Tally};
+
+const USAGE: &str = "Usage: replay [--base-url <url>] [--api-key <key>] <recording.ndjson>...";
+
+/// Re-sends recorded requests to a running instance and prints the responses that changed.
+#[rocket::main]
+async fn main() -> Result<ExitCode, Box<dyn Error + Send + Sync>> {
+    let mut base_url = "http://localhost:8000".to_string();
+    let mut api_key = None;
+    let mut recordings = Vec::new();
+    // Collected, since Args cannot be held across the awaits below
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>().into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--base-url" => base_url = args.next().ok_or(USAGE)?,
+            "--api-key" => api_key = Some(args.next().ok_or(USAGE)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(ExitCode::SUCCESS);
+            }
+            _ => recordings.push(PathBuf::from(arg)),
+        }
+    }
+    if recordings.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let replay = Replay::new(&base_url, api_key);
+    let mut total = Tally::default();
+    for recording in recordings {
+        let tally = replay.run(&recording).await?;
+        total.matched += tally.matched;
+        total.differed += tally.differed;
+        total.skipped += tally.skipped;
+    }
+    println!(
+        "{} matched, {} differed, {} skipped",
+        total.matched, total.differed, total.skipped
+    );
+    Ok(if total.differed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,70 @@
+use std::io::Read;
+use std::process::ExitCode;
+
+use crate::day::Command;
+use crate::error::ApiError;
+
+/// Runs the solver `args` name, like `day22 rocket`, printing its answer or why it failed.
+pub fn run(args: &[String]) -> ExitCode {
+    let commands = crate::registry().commands();
+    let found = match args {
+        [day, name, args @ ..] => commands
+            .iter()
+            .filter(|(commands_day, _)| commands_day == day)
+            .flat_map(|(_, commands)| commands)
+            .find(|command| command.name == name)
+            .map(|command| (command, args)),
+        _ => None,
+    };
+    let Some((command, args)) = found else {
+        eprintln!("{}", usage(&commands));
+        return ExitCode::from(2);
+    };
+    match (command.run)(args) {
+        Ok(answer) => {
+            println!("{}", answer);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage(commands: &[(&'static str, Vec<Command>)]) -> String {
+    let mut usage = "Usage: cch23 [<day> <command> <args>]\n\n\
+        Serves every day without arguments, runs one solver with them:\n"
+        .to_string();
+    for (day, commands) in commands {
+        for command in commands {
+            usage.push_str(&format!(
+                "  cch23 {} {} {}\n",
+                day, command.name, command.args
+            ));
+        }
+    }
+    usage
+}
+
+/// The argument at `index`, named `name` in the error if it is missing.
+pub fn arg<'a>(args: &'a [String], index: usize, name: &str) -> Result<&'a str, ApiError> {
+    args.get(index)
+        .map(String::as_str)
+        .ok_or_else(|| ApiError::BadRequest(format!("Missing argument <{}>", name)))
+}
+
+/// The contents of the file named by the first argument, or of stdin without one or for `-`.
+pub fn input(args: &[String]) -> Result<String, ApiError> {
+    match args.first().map(String::as_str) {
+        None | Some("-") => {
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|e| ApiError::BadRequest(format!("Could not read stdin: {}", e)))?;
+            Ok(input)
+        }
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| ApiError::BadRequest(format!("Could not read {}: {}", path, e))),
+    }
+}
@@ -0,0 +1,172 @@
+use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder, ZstdEncoder};
+use async_compression::Level;
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use rocket::serde::Deserialize;
+use rocket::tokio::io::BufReader;
+use rocket::{Build, Request, Response, Rocket};
+use tracing::{debug, error};
+
+/// The encodings we compress with, in the order we prefer them when a client accepts several
+/// equally.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Brotli,
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    const ALL: [Encoding; 3] = [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// The best encoding `accept_encoding` allows, honouring its quality values.
+    fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+        let accepted = accept_encoding
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';').map(str::trim);
+                let coding = parts.next()?.to_ascii_lowercase();
+                let quality = parts
+                    .find_map(|param| param.strip_prefix("q="))
+                    .map_or(Some(1.0), |quality| quality.parse::<f32>().ok())?;
+                Some((coding, quality))
+            })
+            .collect::<Vec<_>>();
+        let quality = |encoding: &Encoding| {
+            accepted
+                .iter()
+                .find(|(coding, _)| coding == encoding.name())
+                .or_else(|| accepted.iter().find(|(coding, _)| coding == "*"))
+                .map_or(0.0, |(_, quality)| *quality)
+        };
+        Self::ALL
+            .into_iter()
+            .map(|encoding| (encoding, quality(&encoding)))
+            .filter(|(_, quality)| *quality > 0.0)
+            // Keeps the first of equally accepted encodings, unlike max_by
+            .fold(
+                None,
+                |best: Option<(Encoding, f32)>, candidate| match best {
+                    Some(best) if best.1 >= candidate.1 => Some(best),
+                    _ => Some(candidate),
+                },
+            )
+            .map(|(encoding, _)| encoding)
+    }
+}
+
+/// When to compress, read from the `compression` table of the Rocket config, e.g.
+/// `ROCKET_COMPRESSION={threshold=4096}`.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde", default)]
+struct Settings {
+    /// Responses smaller than this many bytes are sent as they are.
+    threshold: usize,
+    /// Content types, or their prefixes like `image/`, that are compressed already.
+    skip: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            threshold: 1024,
+            skip: [
+                "image/",
+                "audio/",
+                "video/",
+                "font/woff",
+                "application/gzip",
+                "application/zip",
+                "application/zstd",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    }
+}
+
+impl Settings {
+    fn skips(&self, response: &Response<'_>) -> bool {
+        let Some(content_type) = response.content_type() else {
+            return false;
+        };
+        let media_type = content_type.media_type().to_string();
+        self.skip
+            .iter()
+            .any(|skipped| media_type.starts_with(skipped.as_str()))
+    }
+}
+
+/// Compresses responses with brotli, zstd or gzip, whichever the client accepts best.
+///
+/// Leaves alone responses below the threshold, of unknown size like websockets and streams,
+/// already encoded ones and those whose content type is compressed already, like the PNG of
+/// day 11.
+pub struct Compression;
+
+#[rocket::async_trait]
+impl Fairing for Compression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Compression",
+            kind: Kind::Ignite | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        match rocket.figment().extract_inner::<Settings>("compression") {
+            Ok(settings) => Ok(rocket.manage(settings)),
+            Err(e) if e.missing() => Ok(rocket.manage(Settings::default())),
+            Err(e) => {
+                error!("Invalid compression config: {}", e);
+                Err(rocket)
+            }
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(settings) = request.rocket().state::<Settings>() else {
+            return;
+        };
+        if request.method() == Method::Head
+            || [Status::NoContent, Status::NotModified].contains(&response.status())
+            || response.headers().contains("Content-Encoding")
+            || settings.skips(response)
+        {
+            return;
+        }
+        match response.body().preset_size() {
+            Some(size) if size >= settings.threshold => {}
+            _ => return,
+        }
+        // Whether or not this one gets compressed, the next one for another client may be
+        response.adjoin_header(Header::new("Vary", "Accept-Encoding"));
+        let Some(encoding) = request
+            .headers()
+            .get_one("Accept-Encoding")
+            .and_then(Encoding::negotiate)
+        else {
+            return;
+        };
+        debug!(encoding = encoding.name(), "Compressing response");
+        let body = BufReader::new(response.body_mut().take());
+        match encoding {
+            Encoding::Brotli => {
+                // Brotli's own default level is too slow to compress on the fly
+                response.set_streamed_body(BrotliEncoder::with_quality(body, Level::Precise(4)))
+            }
+            Encoding::Zstd => response.set_streamed_body(ZstdEncoder::new(body)),
+            Encoding::Gzip => response.set_streamed_body(GzipEncoder::new(body)),
+        }
+        response.set_header(Header::new("Content-Encoding", encoding.name()));
+    }
+}
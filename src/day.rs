@@ -0,0 +1,174 @@
+use rocket::fairing::AdHoc;
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use rocket::{get, routes, Build, Rocket, Route, State};
+use tracing::error;
+
+use crate::error::ApiError;
+use crate::openapi::{Documented, OpenApi, Operation};
+
+/// A challenge day whose routes get mounted into the server by the [`Registry`].
+#[cfg(feature = "days")]
+pub trait Day {
+    /// Identifier of the day, matching its module name.
+    const NAME: &'static str;
+    /// Path the routes of this day are mounted at.
+    const PATH: &'static str;
+    /// Short summary of what the day's challenge is about.
+    const DESCRIPTION: &'static str;
+    /// Tables this day needs, if it talks to the database.
+    const SCHEMA: Option<&'static str> = None;
+
+    fn routes() -> Vec<Route>;
+
+    /// Adds the state this day's routes require.
+    fn manage(rocket: Rocket<Build>) -> Rocket<Build> {
+        rocket
+    }
+
+    /// Describes the request and response bodies of the routes for the OpenAPI document.
+    fn docs() -> Vec<Operation> {
+        Vec::new()
+    }
+
+    /// Solvers that can also run from the command line, without the server.
+    fn commands() -> Vec<Command> {
+        Vec::new()
+    }
+}
+
+/// A solver run from the command line as `cch23 <day> <name> <args>`, printing its answer.
+pub struct Command {
+    pub name: &'static str,
+    /// The arguments it takes, for the usage.
+    pub args: &'static str,
+    pub run: fn(&[String]) -> Result<String, ApiError>,
+}
+
+struct Registration {
+    info: DayInfo,
+    routes: Vec<Route>,
+    docs: Vec<Operation>,
+    commands: Vec<Command>,
+    mount: fn(Rocket<Build>) -> Rocket<Build>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DayInfo {
+    name: &'static str,
+    path: &'static str,
+    description: &'static str,
+    database: bool,
+    routes: Vec<RouteInfo>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RouteInfo {
+    name: Option<String>,
+    method: String,
+    uri: String,
+}
+
+impl From<&Route> for RouteInfo {
+    fn from(route: &Route) -> Self {
+        RouteInfo {
+            name: route.name.as_ref().map(|name| name.to_string()),
+            method: route.method.to_string(),
+            uri: route.uri.to_string(),
+        }
+    }
+}
+
+/// Collects the days to serve and mounts them into a [`Rocket`].
+#[derive(Default)]
+pub struct Registry {
+    days: Vec<Registration>,
+}
+
+/// Information on every mounted day, managed once the registry is built.
+pub struct Days(pub Vec<DayInfo>);
+
+impl Registry {
+    #[cfg(feature = "days")]
+    pub fn register<D: Day>(mut self) -> Self {
+        // The same routes rocket ends up with, so we can tell their full URIs before mounting
+        let routes = D::routes()
+            .into_iter()
+            .filter_map(|route| route.map_base(|base| format!("{}{}", D::PATH, base)).ok())
+            .collect::<Vec<_>>();
+        self.days.push(Registration {
+            info: DayInfo {
+                name: D::NAME,
+                path: D::PATH,
+                description: D::DESCRIPTION,
+                database: D::SCHEMA.is_some(),
+                routes: routes.iter().map(RouteInfo::from).collect(),
+            },
+            routes,
+            docs: D::docs(),
+            commands: D::commands(),
+            mount: |rocket| D::manage(rocket).mount(D::PATH, D::routes()),
+        });
+        self
+    }
+
+    /// The command line solvers of every registered day, by day name.
+    pub fn commands(self) -> Vec<(&'static str, Vec<Command>)> {
+        self.days
+            .into_iter()
+            .map(|day| (day.info.name, day.commands))
+            .collect()
+    }
+
+    /// The docs that match none of the routes of their day, like after renaming a handler.
+    fn stale_docs(&self) -> Vec<String> {
+        self.days
+            .iter()
+            .flat_map(|day| {
+                day.docs
+                    .iter()
+                    .filter(|docs| !day.routes.iter().any(|route| docs.documents(route)))
+                    .map(|docs| format!("{}::{}", day.info.name, docs.route()))
+            })
+            .collect()
+    }
+
+    pub fn build(self, rocket: Rocket<Build>) -> Rocket<Build> {
+        let stale = self.stale_docs();
+        let infos = self.days.iter().map(|day| day.info.clone()).collect();
+        let documented = self
+            .days
+            .iter()
+            .flat_map(|day| {
+                day.routes.iter().map(|route| Documented {
+                    tag: day.info.name,
+                    route,
+                    operation: day.docs.iter().find(|docs| docs.documents(route)),
+                })
+            })
+            .collect::<Vec<_>>();
+        let openapi = OpenApi::new(
+            "Solutions for the Shuttle Christmas Code Hunt 2023",
+            &documented,
+        );
+        self.days
+            .into_iter()
+            .fold(rocket, |rocket, day| (day.mount)(rocket))
+            .manage(Days(infos))
+            .manage(openapi)
+            .mount("/", routes![days])
+            .mount("/", OpenApi::routes())
+            .attach(AdHoc::try_on_ignite("Route docs", |rocket| async move {
+                if stale.is_empty() {
+                    return Ok(rocket);
+                }
+                error!("Docs match no route: {}", stale.join(", "));
+                Err(rocket)
+            }))
+    }
+}
+
+#[get("/days")]
+fn days(days: &State<Days>) -> Json<Vec<DayInfo>> {
+    Json(days.0.clone())
+}
@@ -1,31 +1,438 @@
-use rocket::{get, Route, routes};
-use rocket::http::Status;
+use std::str::FromStr;
+use std::vec;
+
+use num_bigint::BigInt;
+use rocket::data::{self, ByteUnit, Data, DataStream, FromData, ToByteUnit};
 use rocket::http::uri::fmt::Path;
 use rocket::http::uri::Segments;
+use rocket::http::{Accept, ContentType, MediaType, Status};
+use rocket::response::stream::TextStream;
+use rocket::response::{self, Responder};
+use rocket::serde::json::{serde_json, Json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::io::{AsyncBufReadExt, BufReader};
+use rocket::{get, post, routes, Request, Route};
+use schemars::JsonSchema;
+
+use crate::day::Day;
+use crate::error::ApiError;
+use crate::limits::{self, BATCH};
+use crate::openapi::{Body, Operation};
 
-#[get("/<num..>")]
-fn sled_id<'r>(num: Segments<'r, Path>) -> Result<String, Status> {
-    if num.len() > 20 {
-        return Err(Status::BadRequest);
+/// Larger exponents make big integers that take too long to compute and send.
+const MAX_EXPONENT: u32 = 1024;
+/// Most packet IDs a single sled ID is computed from.
+const MAX_IDS: usize = 20;
+
+/// How the packet IDs are combined before raising the result to the exponent.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+enum Reducer {
+    #[default]
+    Xor,
+    Sum,
+    Product,
+    And,
+    Or,
+}
+
+impl FromStr for Reducer {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xor" => Ok(Reducer::Xor),
+            "sum" => Ok(Reducer::Sum),
+            "product" => Ok(Reducer::Product),
+            "and" => Ok(Reducer::And),
+            "or" => Ok(Reducer::Or),
+            _ => Err(ApiError::BadRequest(format!(
+                "Unknown reducer {}, use xor, sum, product, and or or",
+                s
+            ))),
+        }
     }
-    let nums = match num.into_iter()
-        .map(|s| s.parse::<i32>())
-        .collect::<Result<Vec<i32>, _>>() {
-        Ok(nums) => nums,
-        Err(_) => return Err(Status::BadRequest)
-    };
+}
+
+/// Whether to compute in `i32`, wrapping around like the challenge, or with integers of any size.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+enum Mode {
+    #[default]
+    I32,
+    Big,
+}
+
+impl FromStr for Mode {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "i32" => Ok(Mode::I32),
+            "big" => Ok(Mode::Big),
+            _ => Err(ApiError::BadRequest(format!(
+                "Unknown mode {}, use i32 or big",
+                s
+            ))),
+        }
+    }
+}
+
+/// How a sled ID is computed, from the query of the routes.
+#[derive(Debug, Clone, Copy)]
+struct Options {
+    reducer: Reducer,
+    exponent: u32,
+    mode: Mode,
+}
 
-    match nums.iter().map(|&it| it).reduce(|a, b| a ^ b).map(|it| it.pow(3)) {
-        Some(it) => Ok(it.to_string()),
-        None => Err(Status::BadRequest)
+impl Options {
+    fn new(
+        reducer: Option<&str>,
+        exponent: Option<&str>,
+        mode: Option<&str>,
+    ) -> Result<Self, ApiError> {
+        let exponent = match exponent.map(str::parse::<u32>).unwrap_or(Ok(3)) {
+            Ok(exponent @ 0..=MAX_EXPONENT) => exponent,
+            _ => {
+                return Err(ApiError::BadRequest(format!(
+                    "The exponent must be a number from 0 to {}",
+                    MAX_EXPONENT
+                )))
+            }
+        };
+        Ok(Options {
+            reducer: reducer.map(str::parse).transpose()?.unwrap_or_default(),
+            exponent,
+            mode: mode.map(str::parse).transpose()?.unwrap_or_default(),
+        })
     }
 }
 
+/// The sled ID along with how it was computed.
+///
+/// Numbers are decimal strings, since in big mode they exceed what JSON numbers hold exactly.
+#[derive(Serialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+struct SledId {
+    reducer: Reducer,
+    exponent: u32,
+    mode: Mode,
+    /// The packet IDs combined by the reducer.
+    reduced: String,
+    result: String,
+}
+
+/// Just the result as text by default, all of [`SledId`] for clients that prefer JSON.
+enum SledIdResponse {
+    Text(String),
+    Json(Json<SledId>),
+}
+
+impl<'r> Responder<'r, 'static> for SledIdResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match self {
+            SledIdResponse::Text(text) => text.respond_to(request),
+            SledIdResponse::Json(json) => json.respond_to(request),
+        }
+    }
+}
+
+#[get("/<num..>?<reducer>&<exponent>&<mode>")]
+fn sled_id(
+    num: Segments<'_, Path>,
+    reducer: Option<&str>,
+    exponent: Option<&str>,
+    mode: Option<&str>,
+    accept: Option<&Accept>,
+) -> Result<SledIdResponse, ApiError> {
+    let options = Options::new(reducer, exponent, mode)?;
+    let nums = num
+        .into_iter()
+        .map(|s| {
+            s.parse::<i32>()
+                .map_err(|_| ApiError::BadRequest(format!("{} is not a valid packet ID", s)))
+        })
+        .collect::<Result<Vec<i32>, _>>()?;
+    let sled_id = Day1::sled_id(&nums, options)?;
+    Ok(match accept.map(|accept| accept.preferred().media_type()) {
+        Some(media_type) if *media_type == MediaType::JSON => SledIdResponse::Json(Json(sled_id)),
+        _ => SledIdResponse::Text(sled_id.result),
+    })
+}
+
+/// The items of a batch body, capped by the [`BATCH`] limit.
+///
+/// A JSON array is read whole before the first item, newline-delimited JSON
+/// (`application/x-ndjson`) one line at a time as the items are consumed, so results can be sent
+/// while the rest of the body is still arriving.
+enum Batch<'r> {
+    Array(vec::IntoIter<Value>),
+    Lines {
+        reader: Box<BufReader<DataStream<'r>>>,
+        read: u64,
+        limit: ByteUnit,
+        done: bool,
+    },
+}
+
+impl Batch<'_> {
+    /// The next item, or the error of a line that is not JSON.
+    ///
+    /// Failing to read the body, or reading past the limit, is the last item.
+    async fn next(&mut self) -> Option<Result<Value, ApiError>> {
+        let (reader, read, limit, done) = match self {
+            Batch::Array(items) => return items.next().map(Ok),
+            Batch::Lines {
+                reader,
+                read,
+                limit,
+                done,
+            } => (reader, read, *limit, done),
+        };
+        let mut line = Vec::new();
+        while !*done {
+            line.clear();
+            let result = reader.read_until(b'\n', &mut line).await;
+            let n = match result {
+                Ok(0) => {
+                    *done = true;
+                    return None;
+                }
+                Ok(n) => n,
+                Err(e) => {
+                    *done = true;
+                    return Some(Err(ApiError::BadRequest(format!(
+                        "Could not read body: {}",
+                        e
+                    ))));
+                }
+            };
+            *read += n as u64;
+            if *read > limit.as_u64() {
+                *done = true;
+                return Some(Err(ApiError::PayloadTooLarge(format!(
+                    "Request body exceeds the {} limit of {}",
+                    BATCH, limit
+                ))));
+            }
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            return Some(
+                serde_json::from_slice(&line)
+                    .map_err(|e| ApiError::BadRequest(format!("Line is not valid JSON: {}", e))),
+            );
+        }
+        None
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for Batch<'r> {
+    type Error = ApiError;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = limits::limit(request, BATCH);
+        match request.content_type() {
+            Some(content_type) if content_type.is_json() => {
+                let body = match data.open(limit).into_bytes().await {
+                    Ok(body) if body.is_complete() => body.into_inner(),
+                    Ok(_) => return data::Outcome::Error(limits::exceeded(request, BATCH, limit)),
+                    Err(e) => {
+                        return data::Outcome::Error(
+                            ApiError::BadRequest(format!("Could not read body: {}", e))
+                                .reject(request),
+                        )
+                    }
+                };
+                match serde_json::from_slice::<Vec<Value>>(&body) {
+                    Ok(items) => data::Outcome::Success(Batch::Array(items.into_iter())),
+                    Err(e) => data::Outcome::Error(
+                        ApiError::BadRequest(format!("Body is not a JSON array: {}", e))
+                            .reject(request),
+                    ),
+                }
+            }
+            Some(content_type) if content_type.media_type().sub() == "x-ndjson" => {
+                // One byte past the limit tells a body that exceeds it from one that fills it
+                data::Outcome::Success(Batch::Lines {
+                    reader: Box::new(BufReader::new(data.open(limit + 1.bytes()))),
+                    read: 0,
+                    limit,
+                    done: false,
+                })
+            }
+            _ => {
+                data::Outcome::Error(ApiError::Status(Status::UnsupportedMediaType).reject(request))
+            }
+        }
+    }
+}
+
+/// One line of the batch response, for the item at `index` of the request.
+#[derive(Serialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+struct BatchLine {
+    index: usize,
+    #[serde(flatten)]
+    outcome: BatchOutcome,
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+enum BatchOutcome {
+    Result(String),
+    Error(BatchError),
+}
+
+/// Why an item has no result, with the status and code the single route would answer.
+#[derive(Serialize, JsonSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+struct BatchError {
+    status: u16,
+    code: String,
+    detail: String,
+}
+
+#[post("/batch?<reducer>&<exponent>&<mode>", data = "<batch>")]
+fn batch<'r>(
+    reducer: Option<&str>,
+    exponent: Option<&str>,
+    mode: Option<&str>,
+    mut batch: Batch<'r>,
+) -> Result<(ContentType, TextStream![String + 'r]), ApiError> {
+    let options = Options::new(reducer, exponent, mode)?;
+    let stream = TextStream! {
+        let mut index = 0;
+        while let Some(item) = batch.next().await {
+            let outcome = match item
+                .and_then(Day1::ids)
+                .and_then(|ids| Day1::sled_id(&ids, options))
+            {
+                Ok(sled_id) => BatchOutcome::Result(sled_id.result),
+                Err(e) => BatchOutcome::Error(BatchError {
+                    status: e.status().code,
+                    code: e.code(),
+                    detail: e.to_string(),
+                }),
+            };
+            let line = BatchLine { index, outcome };
+            match serde_json::to_string(&line) {
+                Ok(json) => yield json + "\n",
+                Err(e) => tracing::error!("Could not serialize batch line: {}", e),
+            }
+            index += 1;
+        }
+    };
+    Ok((ContentType::new("application", "x-ndjson"), stream))
+}
 
 pub struct Day1 {}
 
+impl Day for Day1 {
+    const NAME: &'static str = "day1";
+    const PATH: &'static str = "/1";
+    const DESCRIPTION: &'static str = "Cube the bits: XOR packet IDs and cube the result";
+
+    fn routes() -> Vec<Route> {
+        routes![sled_id, batch]
+    }
+
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new(
+                "sled_id",
+                "Cube of the XOR of the packet IDs separated by slashes, or another reducer and \
+                exponent; with `Accept: application/json` along with the reduced value",
+            )
+            .parameter::<Reducer>("reducer")
+            .parameter::<u32>("exponent")
+            .parameter::<Mode>("mode")
+            .response(Body::Text),
+            Operation::new(
+                "batch",
+                "Sled IDs of many packet ID lists, sent as a JSON array of arrays or as one array \
+                per line with `Content-Type: application/x-ndjson`; streams one line per list \
+                with either its result or its error",
+            )
+            .parameter::<Reducer>("reducer")
+            .parameter::<u32>("exponent")
+            .parameter::<Mode>("mode")
+            .request(Body::json::<Vec<Vec<i32>>>())
+            .response(Body::Binary("application/x-ndjson")),
+        ]
+    }
+}
+
 impl Day1 {
-    pub fn routes() -> Vec<Route> {
-        routes![sled_id]
+    fn ids(item: Value) -> Result<Vec<i32>, ApiError> {
+        let Value::Array(ids) = item else {
+            return Err(ApiError::BadRequest(format!(
+                "{} is not an array of packet IDs",
+                item
+            )));
+        };
+        ids.iter()
+            .map(|id| {
+                id.as_i64()
+                    .and_then(|id| i32::try_from(id).ok())
+                    .ok_or_else(|| ApiError::BadRequest(format!("{} is not a valid packet ID", id)))
+            })
+            .collect()
     }
-}
\ No newline at end of file
+
+    fn sled_id(ids: &[i32], options: Options) -> Result<SledId, ApiError> {
+        let Options {
+            reducer,
+            exponent,
+            mode,
+        } = options;
+        if ids.is_empty() {
+            return Err(ApiError::BadRequest(
+                "At least one packet ID is required".to_string(),
+            ));
+        }
+        if ids.len() > MAX_IDS {
+            return Err(ApiError::BadRequest(format!(
+                "At most {} packet IDs are allowed",
+                MAX_IDS
+            )));
+        }
+        let (reduced, result) = match mode {
+            Mode::I32 => {
+                // Wraps around on overflow, as the challenge's answers do
+                let reduced = ids[1..].iter().fold(ids[0], |a, &b| match reducer {
+                    Reducer::Xor => a ^ b,
+                    Reducer::Sum => a.wrapping_add(b),
+                    Reducer::Product => a.wrapping_mul(b),
+                    Reducer::And => a & b,
+                    Reducer::Or => a | b,
+                });
+                let result = reduced.wrapping_pow(exponent);
+                (reduced.to_string(), result.to_string())
+            }
+            Mode::Big => {
+                let reduced = ids[1..].iter().map(|&id| BigInt::from(id)).fold(
+                    BigInt::from(ids[0]),
+                    |a, b| match reducer {
+                        Reducer::Xor => a ^ b,
+                        Reducer::Sum => a + b,
+                        Reducer::Product => a * b,
+                        Reducer::And => a & b,
+                        Reducer::Or => a | b,
+                    },
+                );
+                let result = reduced.pow(exponent);
+                (reduced.to_string(), result.to_string())
+            }
+        };
+        Ok(SledId {
+            reducer,
+            exponent,
+            mode,
+            reduced,
+            result,
+        })
+    }
+}
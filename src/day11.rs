@@ -1,71 +1,74 @@
 use std::io::Cursor;
 
 use image::GenericImageView;
-use rocket::form::Form;
-use rocket::fs::{relative, NamedFile, TempFile};
-use rocket::http::Status;
-use rocket::tokio::io::AsyncReadExt;
-use rocket::{get, post, routes, FromForm, Route};
+use rocket::fs::{relative, NamedFile};
+use rocket::tokio::task;
+use rocket::{get, post, routes, Route};
+
+use crate::day::Day;
+use crate::drain::Job;
+use crate::error::ApiError;
+use crate::limits::Image;
+use crate::openapi::{Body, Operation};
 
 #[get("/assets/decoration.png")]
-async fn decoration() -> Result<NamedFile, Status> {
+async fn decoration() -> Result<NamedFile, ApiError> {
     let file = relative!("assets/decoration.png");
-    NamedFile::open(file).await.map_err(|e| {
-        eprintln!("Could not open decoration.png: {:?}", e);
-        Status::InternalServerError
-    })
-}
-
-#[derive(FromForm)]
-struct RedPixelsRequest<'r> {
-    image: TempFile<'r>,
+    NamedFile::open(file)
+        .await
+        .map_err(|e| ApiError::internal("Could not open decoration.png", e))
 }
 
-#[post("/red_pixels", data = "<request>")]
-async fn count_red_pixels(request: Form<RedPixelsRequest<'_>>) -> Result<String, Status> {
-    let mut buffer = Vec::new();
-    request
-        .image
-        .open()
+#[post("/red_pixels", data = "<image>")]
+async fn count_red_pixels(job: Job, image: Image) -> Result<String, ApiError> {
+    task::spawn_blocking(move || Day11::count_red_pixels(&job, image))
         .await
-        .map_err(|e| {
-            eprintln!("Could not open image: {:?}", e);
-            Status::InternalServerError
-        })?
-        .read_to_end(&mut buffer)
-        .await
-        .map_err(|e| {
-            eprintln!("Could not read image: {:?}", e);
-            Status::InternalServerError
-        })?;
-
-    let image = image::io::Reader::new(Cursor::new(buffer))
-        .with_guessed_format()
-        .map_err(|e| {
-            eprintln!("Could not guess format: {:?}", e);
-            Status::InternalServerError
-        })?
-        .decode()
-        .map_err(|e| {
-            eprintln!("Could not decode image: {:?}", e);
-            Status::BadRequest
-        })?;
-    let red_pixels = image
-        .pixels()
-        .filter(|(_x, _y, pixel)| {
-            let red = pixel[0] as u16;
-            let green = pixel[1] as u16;
-            let blue = pixel[2] as u16;
-            red > green + blue
-        })
-        .count();
-    Ok(red_pixels.to_string())
+        .map_err(|e| ApiError::internal("Could not process image", e))?
 }
 
 pub struct Day11 {}
 
-impl Day11 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day11 {
+    const NAME: &'static str = "day11";
+    const PATH: &'static str = "/11";
+    const DESCRIPTION: &'static str = "Serving static files and counting red pixels in images";
+
+    fn routes() -> Vec<Route> {
         routes![decoration, count_red_pixels]
     }
+
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("decoration", "The decoration image")
+                .response(Body::Binary("image/png")),
+            Operation::new(
+                "count_red_pixels",
+                "Number of magical red pixels in the image",
+            )
+            .request(Body::Upload("image"))
+            .response(Body::Text),
+        ]
+    }
+}
+
+impl Day11 {
+    fn count_red_pixels(job: &Job, image: Image) -> Result<String, ApiError> {
+        job.checkpoint()?;
+        let image = image::io::Reader::new(Cursor::new(image.0))
+            .with_guessed_format()
+            .map_err(|e| ApiError::internal("Could not guess format", e))?
+            .decode()
+            .map_err(|e| ApiError::BadRequest(format!("Could not decode image: {}", e)))?;
+        job.checkpoint()?;
+        let red_pixels = image
+            .pixels()
+            .filter(|(_x, _y, pixel)| {
+                let red = pixel[0] as u16;
+                let green = pixel[1] as u16;
+                let blue = pixel[2] as u16;
+                red > green + blue
+            })
+            .count();
+        Ok(red_pixels.to_string())
+    }
 }
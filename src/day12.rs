@@ -1,16 +1,22 @@
 use std::time::SystemTime;
 
+use crate::auth::{scope, Authorized, Scope};
+use crate::day::Day;
 use crate::infrastructure::Infrastructure;
+use crate::openapi::{Body, Operation};
 use chrono::{DateTime, Datelike, Utc, Weekday};
-use rocket::http::Status;
 use rocket::serde::json::Json;
 use rocket::serde::Deserialize;
 use rocket::{get, post, routes, Route, State};
+use schemars::JsonSchema;
 use serde::Serialize;
+use tracing::debug;
 use ulid::Ulid;
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::error::ApiError;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 struct UlidGenerationDatesResponse {
     #[serde(rename = "christmas eve")]
     christmas_eve: usize,
@@ -22,26 +28,29 @@ struct UlidGenerationDatesResponse {
 }
 
 #[post("/save/<key>")]
-fn store(key: &str, infrastructure: &State<Infrastructure>) -> Status {
+fn store(
+    _authorized: Authorized<scope::Write>,
+    key: &str,
+    infrastructure: &State<Infrastructure>,
+) -> Result<(), ApiError> {
     infrastructure
         .persist
         .save(key, SystemTime::now())
-        .map(|_| Status::Ok)
-        .unwrap_or(Status::BadRequest)
+        .map_err(|e| ApiError::BadRequest(format!("Could not save {}: {}", key, e)))
 }
 
 #[get("/load/<key>")]
-fn load(key: &str, infrastructure: &State<Infrastructure>) -> Result<String, Status> {
+fn load(key: &str, infrastructure: &State<Infrastructure>) -> Result<String, ApiError> {
     let persisted_value = infrastructure.persist.load::<SystemTime>(key);
-    println!("Persisted value: {:?}", persisted_value);
+    debug!(key, ?persisted_value, "Loaded persisted value");
     persisted_value
-        .map(|value| SystemTime::now().duration_since(value).unwrap())
+        .map(|value| SystemTime::now().duration_since(value).unwrap_or_default())
         .map(|duration| duration.as_secs().to_string())
-        .map_err(|_| Status::NotFound)
+        .map_err(|_| ApiError::NotFound(format!("Nothing saved for {}", key)))
 }
 
 #[post("/ulids", data = "<ulids>")]
-fn ulids(ulids: Json<Vec<String>>) -> Result<Json<Vec<String>>, Status> {
+fn ulids(ulids: Json<Vec<String>>) -> Result<Json<Vec<String>>, ApiError> {
     let parsed_ulids = Day12::parse_ulids(ulids)?;
     let mut uuids = parsed_ulids
         .iter()
@@ -58,7 +67,7 @@ fn ulids(ulids: Json<Vec<String>>) -> Result<Json<Vec<String>>, Status> {
 fn generation_dates(
     weekday: u8,
     ulids: Json<Vec<String>>,
-) -> Result<Json<UlidGenerationDatesResponse>, Status> {
+) -> Result<Json<UlidGenerationDatesResponse>, ApiError> {
     let parsed_ulids = Day12::parse_ulids(ulids)?;
     Ok(Json::from(UlidGenerationDatesResponse {
         christmas_eve: parsed_ulids
@@ -82,23 +91,50 @@ fn generation_dates(
 
 pub struct Day12 {}
 
-impl Day12 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day12 {
+    const NAME: &'static str = "day12";
+    const PATH: &'static str = "/12";
+    const DESCRIPTION: &'static str = "Persisted timekeeping, ULIDs and UUIDs";
+
+    fn routes() -> Vec<Route> {
         routes![store, load, ulids, generation_dates]
     }
 
-    fn parse_ulids(ulids: Json<Vec<String>>) -> Result<Vec<Ulid>, Status> {
-        let parsed_ulids: Result<Vec<Ulid>, _> =
-            ulids.iter().map(|ulid| Ulid::from_string(ulid)).collect();
-        if parsed_ulids.is_err() {
-            return Err(Status::BadRequest);
-        }
-        Ok(parsed_ulids.unwrap())
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("store", "Remembers the current time for the key")
+                .requires(Scope::Write),
+            Operation::new("load", "Seconds elapsed since the key was saved")
+                .response(Body::Text),
+            Operation::new("ulids", "ULIDs converted to UUIDs in reverse order")
+                .request(Body::json::<Vec<String>>())
+                .response(Body::json::<Vec<String>>()),
+            Operation::new(
+                "generation_dates",
+                "Counts of ULIDs generated on Christmas Eve, on the weekday, in the future and with an odd LSB",
+            )
+            .parameter::<u8>("weekday")
+            .request(Body::json::<Vec<String>>())
+            .response(Body::json::<UlidGenerationDatesResponse>()),
+        ]
+    }
+}
+
+impl Day12 {
+    fn parse_ulids(ulids: Json<Vec<String>>) -> Result<Vec<Ulid>, ApiError> {
+        ulids
+            .iter()
+            .map(|ulid| {
+                Ulid::from_string(ulid).map_err(|e| {
+                    ApiError::BadRequest(format!("{} is not a valid ULID: {}", ulid, e))
+                })
+            })
+            .collect()
     }
 
     fn is_christmas_eve(datetime: &SystemTime) -> bool {
         let datetime: DateTime<Utc> = datetime.to_owned().into();
-        return datetime.month() == 12 && datetime.day() == 24;
+        datetime.month() == 12 && datetime.day() == 24
     }
 
     fn is_weekday(datetime: &SystemTime, weekday: u8) -> bool {
@@ -115,6 +151,6 @@ impl Day12 {
     }
 
     fn is_in_the_future(datetime: &SystemTime) -> bool {
-        return datetime > &SystemTime::now();
+        datetime > &SystemTime::now()
     }
 }
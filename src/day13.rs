@@ -1,9 +1,13 @@
-use rocket::http::Status;
 use rocket::serde::{json::Json, Deserialize, Serialize};
 use rocket::{get, post, routes, Route, State};
+use schemars::JsonSchema;
 use sqlx::{query, Encode, Executor, FromRow, QueryBuilder, Row};
 
-use crate::infrastructure::Infrastructure;
+use crate::auth::{scope, Authorized, Scope};
+use crate::day::Day;
+use crate::error::ApiError;
+use crate::infrastructure::{with_pool, Database, Infrastructure};
+use crate::openapi::{Body, Operation};
 
 const SCHEMA: &str = "DROP TABLE IF EXISTS orders;
 CREATE TABLE orders (
@@ -14,19 +18,19 @@ CREATE TABLE orders (
 );";
 
 #[get("/sql")]
-async fn sql(infrastructure: &State<Infrastructure>) -> Result<String, Status> {
-    let value: i32 = query("SELECT 20231213;")
-        .fetch_one(&infrastructure.postgres)
+async fn sql(infrastructure: &State<Infrastructure>) -> Result<String, ApiError> {
+    let value: i32 = with_pool!(&infrastructure.database, |pool| query("SELECT 20231213;")
+        .fetch_one(pool)
         .await
-        .map_err(|err| {
-            eprintln!("Could not execute query: {}", err);
-            Status::InternalServerError
-        })?
-        .get(0);
+        .map(|row| row.get(0)))
+    .map_err(|source| ApiError::Database {
+        context: "Could not execute query",
+        source,
+    })?;
     Ok(value.to_string())
 }
 
-#[derive(Serialize, Deserialize, Debug, FromRow, Encode)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, FromRow, Encode)]
 struct Order {
     id: i32,
     region_id: i32,
@@ -35,59 +39,64 @@ struct Order {
 }
 
 #[post("/reset")]
-async fn reset(infrastructure: &State<Infrastructure>) -> Status {
-    infrastructure
-        .postgres
-        .execute(SCHEMA)
-        .await
-        .map(|_| Status::Ok)
-        .unwrap_or_else(|err| {
-            eprintln!("Could not reset database: {}", err);
-            Status::InternalServerError
-        })
+async fn reset(
+    _authorized: Authorized<scope::Admin>,
+    infrastructure: &State<Infrastructure>,
+) -> Result<(), ApiError> {
+    Day13::reset(&infrastructure.database).await
 }
 
 #[post("/orders", data = "<orders>")]
-async fn add_orders(orders: Json<Vec<Order>>, infrastructure: &State<Infrastructure>) -> Status {
+async fn add_orders(
+    _authorized: Authorized<scope::Write>,
+    orders: Json<Vec<Order>>,
+    infrastructure: &State<Infrastructure>,
+) -> Result<(), ApiError> {
     let orders: Vec<Order> = orders.0;
-    let mut query = QueryBuilder::new("INSERT INTO orders (id, region_id, gift_name, quantity) ");
-    query.push_values(orders, |mut b, order| {
-        b.push_bind(order.id)
-            .push_bind(order.region_id)
-            .push_bind(order.gift_name)
-            .push_bind(order.quantity);
-    });
-    query
-        .build()
-        .execute(&infrastructure.postgres)
-        .await
-        .map(|_| Status::Ok)
-        .unwrap_or_else(|err| {
-            eprintln!("Could not execute query: {}", err);
-            Status::InternalServerError
-        })
+    if orders.is_empty() {
+        return Ok(());
+    }
+    with_pool!(&infrastructure.database, |pool| {
+        let mut query =
+            QueryBuilder::new("INSERT INTO orders (id, region_id, gift_name, quantity) ");
+        query.push_values(orders, |mut b, order| {
+            b.push_bind(order.id)
+                .push_bind(order.region_id)
+                .push_bind(order.gift_name)
+                .push_bind(order.quantity);
+        });
+        query.build().execute(pool).await.map(|_| ())
+    })
+    .map_err(|source| ApiError::Database {
+        context: "Could not execute query",
+        source,
+    })
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 struct TotalOrders {
     total: i64,
 }
 #[get("/orders/total")]
-async fn total_orders(infrastructure: &State<Infrastructure>) -> Result<Json<TotalOrders>, Status> {
+async fn total_orders(
+    infrastructure: &State<Infrastructure>,
+) -> Result<Json<TotalOrders>, ApiError> {
     // Create a query that adds up all order.quantity values
-    let total: i64 = query("SELECT SUM(quantity) FROM orders;")
-        .fetch_one(&infrastructure.postgres)
-        .await
-        .map_err(|err| {
-            eprintln!("Could not execute query: {}", err);
-            Status::InternalServerError
-        })?
-        .get(0);
+    let total: i64 = with_pool!(&infrastructure.database, |pool| query(
+        "SELECT SUM(quantity) FROM orders;"
+    )
+    .fetch_one(pool)
+    .await
+    .map(|row| row.get(0)))
+    .map_err(|source| ApiError::Database {
+        context: "Could not execute query",
+        source,
+    })?;
 
     Ok(TotalOrders { total }.into())
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 struct MostPopular {
     popular: Option<String>,
 }
@@ -99,21 +108,22 @@ struct QueryResult {
 }
 
 #[get("/orders/popular")]
-async fn most_popular(infrastructure: &State<Infrastructure>) -> Result<Json<MostPopular>, Status> {
-    let result: Vec<QueryResult> = query(
+async fn most_popular(
+    infrastructure: &State<Infrastructure>,
+) -> Result<Json<MostPopular>, ApiError> {
+    let result: Vec<QueryResult> = with_pool!(&infrastructure.database, |pool| query(
         "SELECT gift_name, SUM(quantity) as total_quantity FROM orders GROUP BY gift_name ORDER BY total_quantity DESC LIMIT 1;",
     )
-    .fetch_all(&infrastructure.postgres)
+    .fetch_all(pool)
     .await
-    .map(|row| {
-        row.iter()
+    .and_then(|rows| {
+        rows.iter()
             .map(QueryResult::from_row)
-            .map(Result::unwrap) // If this panics, the query does not match the struct
-            .collect::<Vec<QueryResult>>()
-    })
-    .map_err(|err| {
-        eprintln!("Could not execute query: {}", err);
-        Status::InternalServerError
+            .collect::<Result<Vec<QueryResult>, _>>()
+    }))
+    .map_err(|source| ApiError::Database {
+        context: "Could not execute query",
+        source,
     })?;
     if result.is_empty() {
         return Ok(MostPopular { popular: None }.into());
@@ -126,8 +136,39 @@ async fn most_popular(infrastructure: &State<Infrastructure>) -> Result<Json<Mos
 
 pub struct Day13 {}
 
-impl Day13 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day13 {
+    const NAME: &'static str = "day13";
+    const PATH: &'static str = "/13";
+    const DESCRIPTION: &'static str = "Gift orders stored in SQL";
+    const SCHEMA: Option<&'static str> = Some(SCHEMA);
+
+    fn routes() -> Vec<Route> {
         routes![reset, sql, add_orders, total_orders, most_popular]
     }
+
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("reset", "Recreates the orders table").requires(Scope::Admin),
+            Operation::new("sql", "Result of a trivial query").response(Body::Text),
+            Operation::new("add_orders", "Stores the orders")
+                .request(Body::json::<Vec<Order>>())
+                .requires(Scope::Write),
+            Operation::new("total_orders", "Total quantity over all orders")
+                .response(Body::json::<TotalOrders>()),
+            Operation::new("most_popular", "Gift with the highest total quantity")
+                .response(Body::json::<MostPopular>()),
+        ]
+    }
+}
+
+impl Day13 {
+    /// Drops and recreates the tables of this day.
+    pub async fn reset(database: &Database) -> Result<(), ApiError> {
+        with_pool!(database, |pool| pool.execute(SCHEMA).await.map(|_| ())).map_err(|source| {
+            ApiError::Database {
+                context: "Could not reset database",
+                source,
+            }
+        })
+    }
 }
@@ -2,26 +2,64 @@ use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::{post, routes, Route};
 use rocket_dyn_templates::{context, Template};
+use schemars::JsonSchema;
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::day::Day;
+use crate::openapi::{Body, Operation};
+use crate::security::WithPolicy;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(rename = "HtmlContent")]
 struct Request {
     content: String,
 }
 
+/// Keeps whatever HTML ends up in the rendered page from running scripts, loading anything or
+/// navigating anywhere.
+const POLICY: &str = "default-src 'none'; base-uri 'none'; form-action 'none'; \
+    frame-ancestors 'none'; sandbox";
+
 #[post("/unsafe", data = "<body>")]
-fn render_unsafe(body: Json<Request>) -> Template {
-    Template::render("day14_unsafe", context! {content: &body.content})
+fn render_unsafe(body: Json<Request>) -> WithPolicy<Template> {
+    WithPolicy(
+        POLICY,
+        Template::render("day14_unsafe", context! {content: &body.content}),
+    )
 }
 
 #[post("/safe", data = "<body>")]
-fn render_safe(body: Json<Request>) -> Template {
-    Template::render("day14_safe", context! {content: &body.content})
+fn render_safe(body: Json<Request>) -> WithPolicy<Template> {
+    WithPolicy(
+        POLICY,
+        Template::render("day14_safe", context! {content: &body.content}),
+    )
 }
 
 pub struct Day14 {}
 
-impl Day14 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day14 {
+    const NAME: &'static str = "day14";
+    const PATH: &'static str = "/14";
+    const DESCRIPTION: &'static str = "Rendering safe and unsafe HTML templates";
+
+    fn routes() -> Vec<Route> {
         routes![render_unsafe, render_safe]
     }
+
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new(
+                "render_unsafe",
+                "Page with the content embedded without escaping",
+            )
+            .request(Body::json::<Request>())
+            .response(Body::Html),
+            Operation::new(
+                "render_safe",
+                "Page with the content embedded as escaped HTML",
+            )
+            .request(Body::json::<Request>())
+            .response(Body::Html),
+        ]
+    }
 }
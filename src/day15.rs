@@ -1,15 +1,24 @@
 use regex::Regex;
 use rocket::http::Status;
 use rocket::response::status::BadRequest;
-use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::serde::json::{serde_json, Json};
+use rocket::serde::{Deserialize, Serialize};
 use rocket::{post, routes, Route};
+use schemars::JsonSchema;
+use tracing::debug;
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::cli;
+use crate::day::{Command, Day};
+use crate::error::ApiError;
+use crate::openapi::{Body, Operation};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[schemars(rename = "PasswordInput")]
 struct Request {
     input: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 enum NiceResult {
     #[serde(rename = "nice")]
     Nice,
@@ -17,12 +26,13 @@ enum NiceResult {
     Naughty,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[schemars(rename = "NiceResponse")]
 struct Response {
     result: NiceResult,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 struct GameResponse {
     result: NiceResult,
     reason: String,
@@ -33,147 +43,150 @@ const FORBIDDEN_SUBSTRINGS: [&str; 4] = ["ab", "cd", "pq", "xy"];
 
 #[post("/nice", data = "<request>")]
 fn nice(request: Json<Request>) -> Result<Json<Response>, BadRequest<Json<Response>>> {
-    println!("Got a nice request: {:?}", request);
-    let contains_at_least_three_vowels = request
-        .input
-        .to_lowercase()
-        .chars()
-        .filter(|c| VOWELS.contains(c))
-        .count()
-        >= 3;
-    let has_letters_that_appear_twice_in_a_row =
-        Day15::has_letters_that_appear_twice_in_a_row(&request.input);
-    let has_forbidden_substrings = FORBIDDEN_SUBSTRINGS
-        .iter()
-        .any(|s| request.input.contains(s));
-    let result = if contains_at_least_three_vowels
-        && has_letters_that_appear_twice_in_a_row
-        && !has_forbidden_substrings
-    {
-        NiceResult::Nice
-    } else {
-        NiceResult::Naughty
-    };
-
-    match result {
-        NiceResult::Nice => Ok(Json(Response { result })),
-        NiceResult::Naughty => Err(BadRequest(Json(Response { result }))),
+    debug!(?request, "Checking if input is nice");
+    match Day15::nice(&request.input) {
+        NiceResult::Nice => Ok(Json(Response {
+            result: NiceResult::Nice,
+        })),
+        result => Err(BadRequest(Json(Response { result }))),
     }
 }
 
 #[post("/game", data = "<request>")]
 fn game(request: Json<Request>) -> Result<Json<GameResponse>, (Status, Json<GameResponse>)> {
-    if request.input.len() < 7 {
-        return Err((
-            Status::BadRequest,
+    match Day15::game(&request.input) {
+        Ok(reason) => Ok(Json(GameResponse {
+            result: NiceResult::Nice,
+            reason: reason.to_string(),
+        })),
+        Err((status, reason)) => Err((
+            status,
             Json(GameResponse {
                 result: NiceResult::Naughty,
-                reason: "8 chars".to_string(),
+                reason: reason.to_string(),
             }),
-        ));
+        )),
     }
+}
 
-    if !request.input.chars().any(|c| c.is_uppercase())
-        || !request.input.chars().any(|c| c.is_lowercase())
-        || !request.input.chars().any(|c| c.is_ascii_digit())
-    {
-        return Err((
-            Status::BadRequest,
-            Json(GameResponse {
-                result: NiceResult::Naughty,
-                reason: "more types of chars".to_string(),
-            }),
-        ));
-    }
+pub struct Day15 {}
 
-    if request.input.chars().filter(|c| c.is_ascii_digit()).count() < 5 {
-        return Err((
-            Status::BadRequest,
-            Json(GameResponse {
-                result: NiceResult::Naughty,
-                reason: "55555".to_string(),
-            }),
-        ));
-    }
+impl Day for Day15 {
+    const NAME: &'static str = "day15";
+    const PATH: &'static str = "/15";
+    const DESCRIPTION: &'static str = "Naughty or nice password validation";
 
-    if Day15::find_integers(&request.input).iter().sum::<i32>() != 2023 {
-        return Err((
-            Status::BadRequest,
-            Json(GameResponse {
-                result: NiceResult::Naughty,
-                reason: "math is hard".to_string(),
-            }),
-        ));
-    }
-
-    if !Day15::is_joyful(&request.input) {
-        return Err((
-            Status::NotAcceptable,
-            Json(GameResponse {
-                result: NiceResult::Naughty,
-                reason: "not joyful enough".to_string(),
-            }),
-        ));
+    fn routes() -> Vec<Route> {
+        routes![nice, game]
     }
 
-    if !Day15::contains_sandwich(&request.input) {
-        return Err((
-            Status::UnavailableForLegalReasons,
-            Json(GameResponse {
-                result: NiceResult::Naughty,
-                reason: "illegal: no sandwich".to_string(),
-            }),
-        ));
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("nice", "Whether the input is a nice string")
+                .request(Body::json::<Request>())
+                .response(Body::json::<Response>()),
+            Operation::new(
+                "game",
+                "Whether the input passes all password rules, and which failed",
+            )
+            .request(Body::json::<Request>())
+            .response(Body::json::<GameResponse>()),
+        ]
     }
 
-    if !request
-        .input
-        .chars()
-        .any(|c| matches!(c, '\u{2980}'..='\u{2BFF}'))
-    {
-        return Err((
-            Status::RangeNotSatisfiable,
-            Json(GameResponse {
-                result: NiceResult::Naughty,
-                reason: "outranged".to_string(),
-            }),
-        ));
+    fn commands() -> Vec<Command> {
+        vec![
+            Command {
+                name: "nice",
+                args: "<input>",
+                run: |args| {
+                    Day15::answer(&Response {
+                        result: Day15::nice(cli::arg(args, 0, "input")?),
+                    })
+                },
+            },
+            Command {
+                name: "game",
+                args: "<input>",
+                run: |args| {
+                    Day15::answer(&match Day15::game(cli::arg(args, 0, "input")?) {
+                        Ok(reason) => GameResponse {
+                            result: NiceResult::Nice,
+                            reason: reason.to_string(),
+                        },
+                        Err((_, reason)) => GameResponse {
+                            result: NiceResult::Naughty,
+                            reason: reason.to_string(),
+                        },
+                    })
+                },
+            },
+        ]
     }
+}
 
-    let has_emoji = Regex::new(r"\p{Emoji_Presentation}")
-        .unwrap()
-        .is_match(&request.input);
-    if !has_emoji {
-        return Err((
-            Status::UpgradeRequired,
-            Json(GameResponse {
-                result: NiceResult::Naughty,
-                reason: "😳".to_string(),
-            }),
-        ));
+impl Day15 {
+    /// The JSON the routes answer with, for the command line.
+    fn answer(response: &impl Serialize) -> Result<String, ApiError> {
+        serde_json::to_string(response).map_err(|e| ApiError::internal("Could not serialize", e))
     }
 
-    if sha256::digest(&request.input).chars().nth_back(0).unwrap() != 'a' {
-        return Err((
-            Status::ImATeapot,
-            Json(GameResponse {
-                result: NiceResult::Naughty,
-                reason: "not a coffee brewer".to_string(),
-            }),
-        ));
+    fn nice(input: &str) -> NiceResult {
+        let contains_at_least_three_vowels = input
+            .to_lowercase()
+            .chars()
+            .filter(|c| VOWELS.contains(c))
+            .count()
+            >= 3;
+        let has_letters_that_appear_twice_in_a_row =
+            Day15::has_letters_that_appear_twice_in_a_row(input);
+        let has_forbidden_substrings = FORBIDDEN_SUBSTRINGS.iter().any(|s| input.contains(s));
+        if contains_at_least_three_vowels
+            && has_letters_that_appear_twice_in_a_row
+            && !has_forbidden_substrings
+        {
+            NiceResult::Nice
+        } else {
+            NiceResult::Naughty
+        }
     }
 
-    Ok(Json(GameResponse {
-        result: NiceResult::Nice,
-        reason: "that's a nice password".to_string(),
-    }))
-}
-
-pub struct Day15 {}
-
-impl Day15 {
-    pub fn routes() -> Vec<Route> {
-        routes![nice, game]
+    /// Why the password is nice, or the status and reason of the first rule it breaks.
+    fn game(input: &str) -> Result<&'static str, (Status, &'static str)> {
+        if input.len() < 7 {
+            return Err((Status::BadRequest, "8 chars"));
+        }
+        if !input.chars().any(|c| c.is_uppercase())
+            || !input.chars().any(|c| c.is_lowercase())
+            || !input.chars().any(|c| c.is_ascii_digit())
+        {
+            return Err((Status::BadRequest, "more types of chars"));
+        }
+        if input.chars().filter(|c| c.is_ascii_digit()).count() < 5 {
+            return Err((Status::BadRequest, "55555"));
+        }
+        if Day15::find_integers(input).iter().sum::<i32>() != 2023 {
+            return Err((Status::BadRequest, "math is hard"));
+        }
+        if !Day15::is_joyful(input) {
+            return Err((Status::NotAcceptable, "not joyful enough"));
+        }
+        if !Day15::contains_sandwich(input) {
+            return Err((Status::UnavailableForLegalReasons, "illegal: no sandwich"));
+        }
+        if !input.chars().any(|c| matches!(c, '\u{2980}'..='\u{2BFF}')) {
+            return Err((Status::RangeNotSatisfiable, "outranged"));
+        }
+        let has_emoji = Regex::new(r"\p{Emoji_Presentation}")
+            .unwrap()
+            .is_match(input);
+        if !has_emoji {
+            return Err((Status::UpgradeRequired, "😳"));
+        }
+        if sha256::digest(input).chars().nth_back(0).unwrap() != 'a' {
+            return Err((Status::ImATeapot, "not a coffee brewer"));
+        }
+        Ok("that's a nice password")
     }
 
     fn has_letters_that_appear_twice_in_a_row(input: &str) -> bool {
@@ -1,11 +1,15 @@
 use rocket::futures::future;
-use rocket::http::Status;
 use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::{get, post, routes, Route, State};
-use sqlx::{query, Encode, Executor, FromRow, PgPool, QueryBuilder, Row};
+use schemars::JsonSchema;
+use sqlx::{query, Encode, Executor, FromRow, QueryBuilder, Row};
 
-use crate::infrastructure::Infrastructure;
+use crate::auth::{scope, Authorized, Scope};
+use crate::day::Day;
+use crate::error::ApiError;
+use crate::infrastructure::{with_pool, Database, Infrastructure};
+use crate::openapi::{Body, Operation};
 
 const SCHEMA: &str = "DROP TABLE IF EXISTS regions;
 DROP TABLE IF EXISTS orders;
@@ -22,13 +26,14 @@ CREATE TABLE orders (
   quantity INT
 );";
 
-#[derive(Serialize, Deserialize, Debug, FromRow, Encode)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, FromRow, Encode)]
 struct Region {
     id: i32,
     name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, FromRow, Encode)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, FromRow, Encode)]
+#[schemars(rename = "RegionOrder")]
 struct Order {
     id: i32,
     region_id: i32,
@@ -37,63 +42,64 @@ struct Order {
 }
 
 #[post("/reset")]
-async fn reset(infrastructure: &State<Infrastructure>) -> Status {
-    infrastructure
-        .postgres
-        .execute(SCHEMA)
-        .await
-        .map(|_| Status::Ok)
-        .unwrap_or_else(|err| {
-            eprintln!("Could not reset database: {}", err);
-            Status::InternalServerError
-        })
+async fn reset(
+    _authorized: Authorized<scope::Admin>,
+    infrastructure: &State<Infrastructure>,
+) -> Result<(), ApiError> {
+    Day18::reset(&infrastructure.database).await
 }
 
 #[post("/orders", data = "<orders>")]
-async fn add_orders(orders: Json<Vec<Order>>, infrastructure: &State<Infrastructure>) -> Status {
+async fn add_orders(
+    _authorized: Authorized<scope::Write>,
+    orders: Json<Vec<Order>>,
+    infrastructure: &State<Infrastructure>,
+) -> Result<(), ApiError> {
     let orders: Vec<Order> = orders.0;
     if orders.is_empty() {
-        return Status::Ok;
+        return Ok(());
     }
-    let mut query = QueryBuilder::new("INSERT INTO orders (id, region_id, gift_name, quantity) ");
-    query.push_values(orders, |mut b, order| {
-        b.push_bind(order.id)
-            .push_bind(order.region_id)
-            .push_bind(order.gift_name)
-            .push_bind(order.quantity);
-    });
-    query
-        .build()
-        .execute(&infrastructure.postgres)
-        .await
-        .map(|_| Status::Ok)
-        .unwrap_or_else(|err| {
-            eprintln!("Could not execute query: {}", err);
-            Status::InternalServerError
-        })
+    with_pool!(&infrastructure.database, |pool| {
+        let mut query =
+            QueryBuilder::new("INSERT INTO orders (id, region_id, gift_name, quantity) ");
+        query.push_values(orders, |mut b, order| {
+            b.push_bind(order.id)
+                .push_bind(order.region_id)
+                .push_bind(order.gift_name)
+                .push_bind(order.quantity);
+        });
+        query.build().execute(pool).await.map(|_| ())
+    })
+    .map_err(|source| ApiError::Database {
+        context: "Could not execute query",
+        source,
+    })
 }
 
 #[post("/regions", data = "<regions>")]
-async fn add_regions(regions: Json<Vec<Region>>, infrastructure: &State<Infrastructure>) -> Status {
+async fn add_regions(
+    _authorized: Authorized<scope::Write>,
+    regions: Json<Vec<Region>>,
+    infrastructure: &State<Infrastructure>,
+) -> Result<(), ApiError> {
     if regions.is_empty() {
-        return Status::Ok;
+        return Ok(());
     }
-    let mut query = QueryBuilder::new("INSERT INTO regions (id, name) ");
-    query.push_values(regions.0, |mut b, region| {
-        b.push_bind(region.id).push_bind(region.name);
-    });
-    query
-        .build()
-        .execute(&infrastructure.postgres)
-        .await
-        .map(|_| Status::Ok)
-        .unwrap_or_else(|err| {
-            eprintln!("Could not execute query: {}", err);
-            Status::InternalServerError
-        })
+    let regions: Vec<Region> = regions.0;
+    with_pool!(&infrastructure.database, |pool| {
+        let mut query = QueryBuilder::new("INSERT INTO regions (id, name) ");
+        query.push_values(regions, |mut b, region| {
+            b.push_bind(region.id).push_bind(region.name);
+        });
+        query.build().execute(pool).await.map(|_| ())
+    })
+    .map_err(|source| ApiError::Database {
+        context: "Could not execute query",
+        source,
+    })
 }
 
-#[derive(Serialize, Deserialize, Debug, FromRow)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, FromRow)]
 struct TotalOrdersPerRegion {
     region: String,
     total: i64,
@@ -102,8 +108,8 @@ struct TotalOrdersPerRegion {
 #[get("/regions/total")]
 async fn total_regions(
     infrastructure: &State<Infrastructure>,
-) -> Result<Json<Vec<TotalOrdersPerRegion>>, Status> {
-    query(
+) -> Result<Json<Vec<TotalOrdersPerRegion>>, ApiError> {
+    with_pool!(&infrastructure.database, |pool| query(
         "SELECT name as region, SUM(quantity) as total 
 FROM orders 
 JOIN regions ON orders.region_id=regions.id
@@ -111,22 +117,21 @@ WHERE quantity != 0
 GROUP BY (regions.id)
 ORDER BY regions.name ASC;",
     )
-    .fetch_all(&infrastructure.postgres)
+    .fetch_all(pool)
     .await
-    .map(|it| {
+    .and_then(|it| {
         it.iter()
             .map(TotalOrdersPerRegion::from_row)
-            .map(Result::unwrap)
-            .collect::<Vec<TotalOrdersPerRegion>>()
-    })
+            .collect::<Result<Vec<TotalOrdersPerRegion>, _>>()
+    }))
     .map(Json::from)
-    .map_err(|err| {
-        eprintln!("Could not execute query: {}", err);
-        Status::InternalServerError
+    .map_err(|source| ApiError::Database {
+        context: "Could not execute query",
+        source,
     })
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 struct TopGifts {
     region: String,
     top_gifts: Vec<String>,
@@ -136,34 +141,40 @@ struct TopGifts {
 async fn top_per_region(
     top: i32,
     infrastructure: &State<Infrastructure>,
-) -> Result<Json<Vec<TopGifts>>, Status> {
-    let regions = query("SELECT * from regions ORDER BY name ASC;")
-        .fetch_all(&infrastructure.postgres)
-        .await
-        .map(|it| {
-            it.iter()
-                .map(Region::from_row)
-                .map(Result::unwrap)
-                .collect::<Vec<_>>()
-        })
-        .map_err(|err| {
-            eprintln!("Could not execute query: {}", err);
-            Status::InternalServerError
-        })?;
-
-    let top_gifts = future::join_all(
+) -> Result<Json<Vec<TopGifts>>, ApiError> {
+    let regions = with_pool!(&infrastructure.database, |pool| query(
+        "SELECT * from regions ORDER BY name ASC;"
+    )
+    .fetch_all(pool)
+    .await
+    .and_then(|it| {
+        it.iter()
+            .map(Region::from_row)
+            .collect::<Result<Vec<_>, _>>()
+    }))
+    .map_err(|source| ApiError::Database {
+        context: "Could not execute query",
+        source,
+    })?;
+
+    let top_gifts = future::try_join_all(
         regions
             .iter()
-            .map(|region| Day18::top_gifts_for_region(region, &top, &infrastructure.postgres)),
+            .map(|region| Day18::top_gifts_for_region(region, &top, &infrastructure.database)),
     )
-    .await;
+    .await?;
     Ok(Json(top_gifts))
 }
 
 pub struct Day18 {}
 
-impl Day18 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day18 {
+    const NAME: &'static str = "day18";
+    const PATH: &'static str = "/18";
+    const DESCRIPTION: &'static str = "Gift orders per region stored in SQL";
+    const SCHEMA: Option<&'static str> = Some(SCHEMA);
+
+    fn routes() -> Vec<Route> {
         routes![
             reset,
             add_orders,
@@ -173,20 +184,60 @@ impl Day18 {
         ]
     }
 
-    async fn top_gifts_for_region(region: &Region, top: &i32, executor: &PgPool) -> TopGifts {
-        let gift_names = query("SELECT gift_name FROM orders WHERE region_id=$1 GROUP BY gift_name ORDER BY SUM(quantity) DESC LIMIT $2;")
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("reset", "Recreates the regions and orders tables")
+                .requires(Scope::Admin),
+            Operation::new("add_orders", "Stores the orders")
+                .request(Body::json::<Vec<Order>>())
+                .requires(Scope::Write),
+            Operation::new("add_regions", "Stores the regions")
+                .request(Body::json::<Vec<Region>>())
+                .requires(Scope::Write),
+            Operation::new(
+                "total_regions",
+                "Total quantity of gifts ordered per region",
+            )
+            .response(Body::json::<Vec<TotalOrdersPerRegion>>()),
+            Operation::new("top_per_region", "The most ordered gifts of every region")
+                .parameter::<i32>("top")
+                .response(Body::json::<Vec<TopGifts>>()),
+        ]
+    }
+}
+
+impl Day18 {
+    /// Drops and recreates the tables of this day.
+    pub async fn reset(database: &Database) -> Result<(), ApiError> {
+        with_pool!(database, |pool| pool.execute(SCHEMA).await.map(|_| ())).map_err(|source| {
+            ApiError::Database {
+                context: "Could not reset database",
+                source,
+            }
+        })
+    }
+
+    async fn top_gifts_for_region(
+        region: &Region,
+        top: &i32,
+        database: &Database,
+    ) -> Result<TopGifts, ApiError> {
+        let gift_names = with_pool!(database, |pool| query("SELECT gift_name FROM orders WHERE region_id=$1 GROUP BY gift_name ORDER BY SUM(quantity) DESC LIMIT $2;")
             .bind(region.id).bind(top)
-            .fetch_all(executor)
+            .fetch_all(pool)
             .await
             .map(|rows| {
                 rows.iter()
                     .map(|row| row.get::<String, &'static str>("gift_name"))
                     .collect::<Vec<String>>()
-            })
-            .unwrap();
-        TopGifts {
+            }))
+            .map_err(|source| ApiError::Database {
+                context: "Could not query top gifts",
+                source,
+            })?;
+        Ok(TopGifts {
             region: String::from(&region.name),
             top_gifts: gift_names,
-        }
+        })
     }
 }
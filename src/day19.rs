@@ -1,17 +1,26 @@
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::RwLock;
 
 use rocket::futures::StreamExt;
 use rocket::serde::json::serde_json;
 use rocket::serde::{Deserialize, Serialize};
-use rocket::{get, post, routes, Route, Shutdown, State};
+use rocket::{get, post, routes, Build, Rocket, Route, Shutdown, State};
+use rocket_ws::frame::{CloseCode, CloseFrame};
 use rocket_ws::{Message, Stream, WebSocket};
+use schemars::JsonSchema;
 use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::broadcast::Receiver;
 use tokio::{
     select,
     sync::broadcast::{channel, Sender},
 };
+use tracing::{debug, warn};
+
+use crate::auth::{scope, Authorized, Scope};
+use crate::day::Day;
+use crate::drain::Accepting;
+use crate::openapi::{Body, Operation};
 
 #[derive(PartialEq, Debug)]
 enum PingState {
@@ -20,21 +29,35 @@ enum PingState {
 }
 
 #[get("/ws/ping")]
-fn ping(ws: WebSocket, day19: &State<Day19>) -> Stream!['_] {
+fn ping(
+    _accepting: Accepting,
+    ws: WebSocket,
+    day19: &State<Day19>,
+    mut end: Shutdown,
+) -> Stream!['_] {
     Stream! { ws => {
-        for await message in ws {
-            match message {
-                Ok(message) => {
-                    if message.is_close() {
-                        break;
-                    }
-                    let response = day19.ping_pong(message);
-                    if response.is_some() {
-                        yield response.unwrap()
+        let mut ws = ws;
+        loop {
+            select! {
+                message = ws.next() => {
+                    match message {
+                        Some(Ok(message)) => {
+                            if message.is_close() {
+                                break;
+                            }
+                            if let Some(response) = day19.ping_pong(message) {
+                                yield response
+                            }
+                        },
+                        Some(Err(e)) => {
+                            warn!("WebSocket error: {}", e);
+                            break;
+                        }
+                        None => break,
                     }
                 },
-                Err(e) => {
-                    eprintln!("WebSocket error: {}", e);
+                _ = &mut end => {
+                    yield Day19::going_away();
                     break;
                 }
             }
@@ -42,11 +65,17 @@ fn ping(ws: WebSocket, day19: &State<Day19>) -> Stream!['_] {
     }}
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone)]
 struct Twit {
     message: String,
 }
 
+#[derive(Serialize, JsonSchema, Debug)]
+struct TwitView<'a> {
+    user: &'a str,
+    message: &'a str,
+}
+
 #[derive(Debug, Clone)]
 struct Broadcast {
     room: i32,
@@ -65,19 +94,20 @@ impl From<(i32, String, Twit)> for Broadcast {
 }
 
 #[post("/reset")]
-fn bird_app_reset(day19: &State<Day19>) -> () {
+fn bird_app_reset(_authorized: Authorized<scope::Admin>, day19: &State<Day19>) {
     day19.reset();
 }
 
 #[get("/views")]
 fn bird_app_views(day19: &State<Day19>) -> String {
-    let string = day19.views().to_string();
-    println!("views: {}", string);
-    string
+    let views = day19.views();
+    debug!(views, "Counted views");
+    views.to_string()
 }
 
 #[get("/ws/room/<room>/user/<user>")]
 fn bird_app_connect<'a>(
+    _accepting: Accepting,
     ws: WebSocket,
     day19: &'a State<Day19>,
     room: i32,
@@ -86,6 +116,7 @@ fn bird_app_connect<'a>(
 ) -> Stream!['a] {
     let mut rx = day19.stream.0.subscribe();
     Stream! { ws =>
+        let _connection = day19.connect(room);
         let mut ws = ws;
         loop {
             select! {
@@ -95,13 +126,11 @@ fn bird_app_connect<'a>(
                             match message {
                                 Message::Ping(ping) => yield Message::Pong(ping.clone()),
                                 Message::Text(ref message) => {
-                                    match serde_json::from_str::<Twit>(message) {
-                                        Ok(twit) => {
-                                            if twit.message.len() <= 128 {
-                                                let  _ = day19.stream.0.send((room, user.to_string(), twit).into());
-                                            }
-                                        },
-                                    _ => {}}
+                                    if let Ok(twit) = serde_json::from_str::<Twit>(message) {
+                                        if twit.message.len() <= 128 {
+                                            let  _ = day19.stream.0.send((room, user.to_string(), twit).into());
+                                        }
+                                    }
                                 },
                                 Message::Close(_) => break,
                                 _ => {}
@@ -114,10 +143,10 @@ fn bird_app_connect<'a>(
                         Ok(ref twit) => {
                             if twit.room == room {
                                 day19.log_view();
-                                let response = serde_json::json!({
-                                    "user": twit.author.clone(),
-                                    "message": twit.twit.message.clone(),
-                                });
+                                let response = TwitView {
+                                    user: &twit.author,
+                                    message: &twit.twit.message,
+                                };
                                 let response = serde_json::to_string(&response).unwrap();
                                 yield Message::Text(response);
                             }
@@ -126,8 +155,10 @@ fn bird_app_connect<'a>(
                         Err(RecvError::Lagged(_)) => continue,
                     }
                 },
-                _ = &mut end => break
-
+                _ = &mut end => {
+                    yield Day19::going_away();
+                    break;
+                }
             }
        }
     }
@@ -137,13 +168,69 @@ pub struct Day19 {
     ping_state: RwLock<PingState>,
     twit_views: AtomicU64,
     stream: (Sender<Broadcast>, Receiver<Broadcast>),
+    clients: RwLock<HashMap<i32, usize>>,
 }
 
-impl Day19 {
-    pub fn routes() -> Vec<Route> {
+/// A client connected to a bird app room, counted until dropped.
+struct Connection<'a> {
+    day19: &'a Day19,
+    room: i32,
+}
+
+impl Drop for Connection<'_> {
+    fn drop(&mut self) {
+        let mut clients = self.day19.clients.write().unwrap();
+        if let Some(count) = clients.get_mut(&self.room) {
+            *count -= 1;
+            if *count == 0 {
+                clients.remove(&self.room);
+            }
+        }
+    }
+}
+
+impl Day for Day19 {
+    const NAME: &'static str = "day19";
+    const PATH: &'static str = "/19";
+    const DESCRIPTION: &'static str = "Websocket ping-pong and a broadcasting bird app";
+
+    fn routes() -> Vec<Route> {
         routes![ping, bird_app_reset, bird_app_views, bird_app_connect]
     }
 
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::websocket::<String>(
+                "ping",
+                "Answers `ping` with `pong` once the game was started with `serve`",
+            )
+            .response(Body::Text),
+            Operation::new("bird_app_reset", "Resets the view counter").requires(Scope::Admin),
+            Operation::new("bird_app_views", "Number of twits delivered to users")
+                .response(Body::Text),
+            Operation::websocket::<Twit>(
+                "bird_app_connect",
+                "Joins the room as the user, broadcasting twits of up to 128 characters",
+            )
+            .parameter::<i32>("room")
+            .response(Body::json::<TwitView>()),
+        ]
+    }
+
+    fn manage(rocket: Rocket<Build>) -> Rocket<Build> {
+        rocket.manage(Day19::default())
+    }
+}
+
+impl Day19 {
+    /// Tells a client its connection ends because the server shuts down.
+    fn going_away() -> Message {
+        Message::Close(Some(CloseFrame {
+            code: CloseCode::Away,
+            reason: "Server is shutting down".into(),
+        }))
+    }
+
     fn ping_pong(&self, message: Message) -> Option<Message> {
         match message {
             Message::Text(text) => match text.as_str() {
@@ -166,14 +253,32 @@ impl Day19 {
         }
     }
 
-    fn reset(&self) {
+    pub fn reset(&self) {
         self.twit_views.store(0, Ordering::Release);
     }
 
-    fn views(&self) -> u64 {
+    pub fn views(&self) -> u64 {
         self.twit_views.load(Ordering::Acquire)
     }
 
+    /// Number of connected clients per room.
+    pub fn clients(&self) -> Vec<(i32, usize)> {
+        let mut clients = self
+            .clients
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(&room, &count)| (room, count))
+            .collect::<Vec<_>>();
+        clients.sort();
+        clients
+    }
+
+    fn connect(&self, room: i32) -> Connection<'_> {
+        *self.clients.write().unwrap().entry(room).or_insert(0) += 1;
+        Connection { day19: self, room }
+    }
+
     fn log_view(&self) {
         self.twit_views
             .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |views| Some(views + 1))
@@ -187,6 +292,7 @@ impl Default for Day19 {
             ping_state: RwLock::new(PingState::None),
             twit_views: AtomicU64::new(0),
             stream: channel::<Broadcast>(4096),
+            clients: RwLock::new(HashMap::new()),
         }
     }
 }
@@ -1,126 +1,212 @@
 use git2::{BranchType, Commit, Repository, TreeWalkMode, TreeWalkResult};
-use rocket::fs::TempFile;
-use rocket::http::Status;
+use rocket::tokio::task;
 use rocket::{post, routes, Route};
 use std::fs::File;
+use std::path::Path;
 use tar::Archive;
 use tempfile::TempDir;
+use tracing::{instrument, warn};
+
+use crate::cli;
+use crate::day::{Command, Day};
+use crate::drain::Job;
+use crate::error::ApiError;
+use crate::limits::Upload;
+use crate::openapi::{Body, Operation};
 
 #[post("/archive_files", data = "<file>")]
-fn archive_files(file: TempFile<'_>) -> Result<String, Status> {
-    Day20::archive_files(file)
+async fn archive_files(job: Job, file: Upload) -> Result<String, ApiError> {
+    Day20::run(job, file, Day20::archive_files).await
 }
 
 #[post("/archive_files_size", data = "<file>")]
-fn archive_files_size(file: TempFile<'_>) -> Result<String, Status> {
-    Day20::archive_files_size(file)
+async fn archive_files_size(job: Job, file: Upload) -> Result<String, ApiError> {
+    Day20::run(job, file, Day20::archive_files_size).await
 }
 
 #[post("/cookie", data = "<file>")]
-fn cookie(file: TempFile<'_>) -> Result<String, Status> {
-    Day20::cookie(file)
+async fn cookie(job: Job, file: Upload) -> Result<String, ApiError> {
+    Day20::run(job, file, Day20::cookie).await
 }
 pub struct Day20 {}
 
-impl Day20 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day20 {
+    const NAME: &'static str = "day20";
+    const PATH: &'static str = "/20";
+    const DESCRIPTION: &'static str = "Unpacking tar archives and searching git history";
+
+    fn routes() -> Vec<Route> {
         routes![archive_files, archive_files_size, cookie]
     }
 
-    fn open_archive(file: TempFile) -> Result<Archive<File>, Status> {
-        if file.path().is_none() {
-            return Err(Status::BadRequest);
-        }
-        let file = File::open(file.path().unwrap());
-        if file.is_err() {
-            eprintln!("Could not open temp file");
-            return Err(Status::InternalServerError);
-        }
-        Ok(Archive::new(file.unwrap()))
+    fn docs() -> Vec<Operation> {
+        let tar = || Body::Binary("application/x-tar");
+        vec![
+            Operation::new("archive_files", "Number of files in the archive")
+                .request(tar())
+                .response(Body::Text),
+            Operation::new("archive_files_size", "Total size of the files in the archive")
+                .request(tar())
+                .response(Body::Text),
+            Operation::new(
+                "cookie",
+                "Author and commit that added `santa` to a `santa.txt` in the archived git repository",
+            )
+            .request(tar())
+            .response(Body::Text),
+        ]
     }
 
-    fn archive_files(file: TempFile<'_>) -> Result<String, Status> {
+    fn commands() -> Vec<Command> {
+        vec![
+            Command {
+                name: "archive_files",
+                args: "<archive.tar>",
+                run: |args| {
+                    Day20::archive_files(
+                        &Job::detached(),
+                        cli::arg(args, 0, "archive.tar")?.as_ref(),
+                    )
+                },
+            },
+            Command {
+                name: "archive_files_size",
+                args: "<archive.tar>",
+                run: |args| {
+                    Day20::archive_files_size(
+                        &Job::detached(),
+                        cli::arg(args, 0, "archive.tar")?.as_ref(),
+                    )
+                },
+            },
+            Command {
+                name: "cookie",
+                args: "<repo.tar>",
+                run: |args| {
+                    Day20::cookie(&Job::detached(), cli::arg(args, 0, "repo.tar")?.as_ref())
+                },
+            },
+        ]
+    }
+}
+
+impl Day20 {
+    /// Runs `work` off the async runtime, holding on to `job` until it is done.
+    async fn run(
+        job: Job,
+        file: Upload,
+        work: fn(&Job, &Path) -> Result<String, ApiError>,
+    ) -> Result<String, ApiError> {
+        task::spawn_blocking(move || work(&job, file.path()))
+            .await
+            .map_err(|e| ApiError::internal("Could not process archive", e))?
+    }
+
+    fn open_archive(file: &Path) -> Result<Archive<File>, ApiError> {
+        File::open(file)
+            .map(Archive::new)
+            .map_err(|e| ApiError::internal("Could not open archive", e))
+    }
+
+    fn archive_files(job: &Job, file: &Path) -> Result<String, ApiError> {
         let mut archive = Self::open_archive(file)?;
-        let entries = archive.entries().map_err(|_| Status::BadRequest)?;
-        Ok(entries
-            .into_iter()
-            .filter(|it| it.is_ok())
-            .count()
-            .to_string())
+        let entries = archive
+            .entries()
+            .map_err(|e| ApiError::BadRequest(format!("Not a valid tar archive: {}", e)))?;
+        let mut count = 0;
+        for entry in entries {
+            job.checkpoint()?;
+            count += usize::from(entry.is_ok());
+        }
+        Ok(count.to_string())
     }
 
-    fn archive_files_size(file: TempFile<'_>) -> Result<String, Status> {
+    fn archive_files_size(job: &Job, file: &Path) -> Result<String, ApiError> {
         let mut archive = Self::open_archive(file)?;
-        let entries = archive.entries().map_err(|_| Status::BadRequest)?;
-
-        Ok(entries
-            .into_iter()
-            .map(|file| {
-                file.and_then(|file| file.header().size())
-                    .unwrap_or_else(|err| {
-                        eprintln!("Could not get size of file: {:?}", err);
-                        0
-                    })
-            })
-            .sum::<u64>()
-            .to_string())
+        let entries = archive
+            .entries()
+            .map_err(|e| ApiError::BadRequest(format!("Not a valid tar archive: {}", e)))?;
+        let mut size = 0;
+        for entry in entries {
+            job.checkpoint()?;
+            size += entry
+                .and_then(|file| file.header().size())
+                .unwrap_or_else(|err| {
+                    warn!("Could not get size of file: {:?}", err);
+                    0
+                });
+        }
+        Ok(size.to_string())
     }
 
-    fn cookie(file: TempFile) -> Result<String, Status> {
+    #[instrument(skip_all)]
+    fn cookie(job: &Job, file: &Path) -> Result<String, ApiError> {
         let mut archive = Day20::open_archive(file)?;
-        tempfile::tempdir()
-            .map_err(|error| {
-                eprintln!("Could not create temporary directory: {:?}", error);
-                Status::InternalServerError
-            })
-            .and_then(|dir| {
-                archive
-                    .unpack(dir.path())
-                    .map_err(|error| {
-                        eprintln!("Could not unpack archive {:?}", error);
-                        Status::InternalServerError
-                    })
-                    .map(|_| Day20::find_commit(dir))
-            })
+        let dir = tempfile::tempdir_in(job.scratch())
+            .map_err(|e| ApiError::internal("Could not create temporary directory", e))?;
+        let unpack_failed = |e| ApiError::BadRequest(format!("Could not unpack archive: {}", e));
+        for entry in archive.entries().map_err(unpack_failed)? {
+            job.checkpoint()?;
+            entry
+                .and_then(|mut entry| entry.unpack_in(dir.path()))
+                .map_err(unpack_failed)?;
+        }
+        Day20::find_commit(job, dir)
     }
 
-    fn find_commit(dir: TempDir) -> String {
-        let repo = Repository::open(dir.path()).unwrap();
-        let branch = repo.find_branch("christmas", BranchType::Local).unwrap();
-        let commit = branch.get().peel_to_commit().unwrap();
-        let result = Day20::traverse_commit(&repo, &commit).unwrap_or("".to_string());
-        dir.close().unwrap();
-        result
+    #[instrument(skip_all)]
+    fn find_commit(job: &Job, dir: TempDir) -> Result<String, ApiError> {
+        let repo = Repository::open(dir.path())
+            .map_err(|e| ApiError::BadRequest(format!("Archive is not a git repository: {}", e)))?;
+        let branch = repo
+            .find_branch("christmas", BranchType::Local)
+            .map_err(|e| ApiError::BadRequest(format!("Branch christmas not found: {}", e)))?;
+        let commit = branch
+            .get()
+            .peel_to_commit()
+            .map_err(|e| ApiError::internal("Could not resolve branch christmas", e))?;
+        let result = Day20::traverse_commit(job, &repo, &commit)?.unwrap_or_default();
+        dir.close()
+            .map_err(|e| ApiError::internal("Could not remove temporary directory", e))?;
+        Ok(result)
     }
 
-    fn traverse_commit(repo: &Repository, commit: &Commit) -> Option<String> {
-        if Day20::is_searched_commit(repo, commit) {
-            return Some(format!(
+    fn traverse_commit(
+        job: &Job,
+        repo: &Repository,
+        commit: &Commit,
+    ) -> Result<Option<String>, ApiError> {
+        job.checkpoint()?;
+        if Day20::is_searched_commit(repo, commit)? {
+            return Ok(Some(format!(
                 "{} {}",
                 commit.author().name().unwrap_or("Unknown Author"),
                 commit.id()
-            ));
+            )));
         }
 
         for commit in commit.parents() {
-            let traversed_commit = Day20::traverse_commit(repo, &commit);
+            let traversed_commit = Day20::traverse_commit(job, repo, &commit)?;
             if traversed_commit.is_some() {
-                return traversed_commit;
+                return Ok(traversed_commit);
             }
         }
-        None
+        Ok(None)
     }
 
-    fn is_searched_commit(repo: &Repository, commit: &Commit) -> bool {
-        let tree = commit.tree().unwrap();
+    fn is_searched_commit(repo: &Repository, commit: &Commit) -> Result<bool, ApiError> {
+        let tree = commit
+            .tree()
+            .map_err(|e| ApiError::internal("Could not read commit tree", e))?;
         let mut found = false;
         tree.walk(TreeWalkMode::PreOrder, |_, entry| {
             if entry.name() == Some("santa.txt") {
-                let object = entry.to_object(repo);
-                let object = object.unwrap();
+                let Ok(object) = entry.to_object(repo) else {
+                    return TreeWalkResult::Ok;
+                };
                 let blob = object.as_blob();
                 if blob.is_none() {
-                    println!(
+                    warn!(
                         "Not a blob: {:?}",
                         entry.name().unwrap_or(entry.id().to_string().as_str())
                     );
@@ -130,7 +216,7 @@ impl Day20 {
                 let content = String::from_utf8(blob.content().to_vec());
 
                 if content.is_err() {
-                    println!(
+                    warn!(
                         "Could not parse santa file {:?}",
                         entry.name().unwrap_or(entry.id().to_string().as_str())
                     );
@@ -144,7 +230,7 @@ impl Day20 {
             }
             TreeWalkResult::Ok
         })
-        .expect("Walk failed");
-        found
+        .map_err(|e| ApiError::internal("Could not walk commit tree", e))?;
+        Ok(found)
     }
 }
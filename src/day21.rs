@@ -1,19 +1,23 @@
 use country_boundaries::{CountryBoundaries, LatLon, BOUNDARIES_ODBL_360X180};
 use isocountry::CountryCode;
-use rocket::http::Status;
-use rocket::{get, routes, Route, State};
+use rocket::{get, routes, Build, Rocket, Route, State};
 use s2::cellid::CellID;
 use s2::latlng::LatLng;
 
+use crate::cli;
+use crate::day::{Command, Day};
+use crate::error::ApiError;
+use crate::openapi::{Body, Operation};
+
 #[get("/coords/<binary>")]
-fn coords_binary(binary: String) -> Result<String, Status> {
-    let binary: u64 = u64::from_str_radix(&binary, 2).map_err(|_| Status::BadRequest)?;
+fn coords_binary(binary: String) -> Result<String, ApiError> {
+    let binary: u64 = Day21::parse_cell_id(&binary)?;
     Day21::coords(binary)
 }
 
 #[get("/country/<binary>")]
-fn country_binary(binary: String, day21: &State<Day21>) -> Result<String, Status> {
-    let binary: u64 = u64::from_str_radix(&binary, 2).map_err(|_| Status::BadRequest)?;
+fn country_binary(binary: String, day21: &State<Day21>) -> Result<String, ApiError> {
+    let binary: u64 = Day21::parse_cell_id(&binary)?;
     day21.country(binary)
 }
 
@@ -29,12 +33,63 @@ impl Default for Day21 {
     }
 }
 
-impl Day21 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day21 {
+    const NAME: &'static str = "day21";
+    const PATH: &'static str = "/21";
+    const DESCRIPTION: &'static str = "S2 cell coordinates and countries";
+
+    fn routes() -> Vec<Route> {
         routes![coords_binary, country_binary]
     }
 
-    fn coords(cell_id: u64) -> Result<String, Status> {
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new(
+                "coords_binary",
+                "Coordinates of the binary S2 cell ID in DMS",
+            )
+            .response(Body::Text),
+            Operation::new("country_binary", "Country the binary S2 cell ID lies in")
+                .response(Body::Text),
+        ]
+    }
+
+    fn commands() -> Vec<Command> {
+        vec![
+            Command {
+                name: "coords",
+                args: "<bits>",
+                run: |args| Day21::coords(Day21::parse_cell_id(cli::arg(args, 0, "bits")?)?),
+            },
+            Command {
+                name: "country",
+                args: "<bits>",
+                run: |args| {
+                    Day21::default().country(Day21::parse_cell_id(cli::arg(args, 0, "bits")?)?)
+                },
+            },
+        ]
+    }
+
+    fn manage(rocket: Rocket<Build>) -> Rocket<Build> {
+        rocket.manage(Day21::default())
+    }
+}
+
+impl Day21 {
+    /// Whether the boundaries know about a country, Germany in this case.
+    pub fn boundaries_loaded(&self) -> bool {
+        LatLon::new(52.52, 13.40)
+            .map(|berlin| !self.country_boundaries.ids(berlin).is_empty())
+            .unwrap_or(false)
+    }
+
+    fn parse_cell_id(binary: &str) -> Result<u64, ApiError> {
+        u64::from_str_radix(binary, 2)
+            .map_err(|_| ApiError::BadRequest(format!("{} is not a binary cell id", binary)))
+    }
+
+    fn coords(cell_id: u64) -> Result<String, ApiError> {
         let cell_id = CellID(cell_id);
         let lat_lng = LatLng::from(cell_id);
         Ok(format!(
@@ -44,15 +99,17 @@ impl Day21 {
         ))
     }
 
-    fn country(&self, cell_id: u64) -> Result<String, Status> {
+    fn country(&self, cell_id: u64) -> Result<String, ApiError> {
         let cell_id = CellID(cell_id);
         let lat_lng = LatLng::from(cell_id);
-        let lat_lng =
-            LatLon::new(lat_lng.lat.deg(), lat_lng.lng.deg()).map_err(|_| Status::BadRequest)?;
+        let lat_lng = LatLon::new(lat_lng.lat.deg(), lat_lng.lng.deg())
+            .map_err(|e| ApiError::BadRequest(format!("Cell is not a valid coordinate: {}", e)))?;
         let matched_ids = self.country_boundaries.ids(lat_lng);
-        let country_code = matched_ids.last().ok_or(Status::NotFound)?;
+        let country_code = matched_ids
+            .last()
+            .ok_or_else(|| ApiError::NotFound("Cell is not within any country".to_string()))?;
         let name = CountryCode::for_alpha2(country_code)
-            .map_err(|_| Status::BadRequest)?
+            .map_err(|_| ApiError::NotFound(format!("Unknown country code {}", country_code)))?
             .name();
 
         Ok(if name == "Brunei Darussalam" {
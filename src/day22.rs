@@ -1,36 +1,79 @@
 use pathfinding::directed::bfs::bfs;
-use rocket::http::Status;
 use rocket::{post, routes, Route};
+use tracing::{instrument, trace};
+
+use crate::cli;
+use crate::day::{Command, Day};
+use crate::error::ApiError;
+use crate::limits::Text;
+use crate::openapi::{Body, Operation};
 
 #[post("/integers", data = "<text>")]
-fn integers(text: String) -> Result<String, Status> {
-    Day22::integers(text)
+fn integers(text: Text) -> Result<String, ApiError> {
+    Day22::integers(text.0)
 }
 
 #[post("/rocket", data = "<text>")]
-fn rocket_path(text: String) -> Result<String, Status> {
-    Day22::rocket(text)
+fn rocket_path(text: Text) -> Result<String, ApiError> {
+    Day22::rocket(text.0)
 }
 
 pub struct Day22 {}
 
-impl Day22 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day22 {
+    const NAME: &'static str = "day22";
+    const PATH: &'static str = "/22";
+    const DESCRIPTION: &'static str =
+        "Finding the lone gift integer and rocket paths between stars";
+
+    fn routes() -> Vec<Route> {
         routes![integers, rocket_path]
     }
 
-    fn integers(text: String) -> Result<String, Status> {
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("integers", "Presents for the one integer without a pair")
+                .request(Body::Text)
+                .response(Body::Text),
+            Operation::new(
+                "rocket_path",
+                "Number of portals and distance of the shortest path from the first to the last star",
+            )
+            .request(Body::Text)
+            .response(Body::Text),
+        ]
+    }
+
+    fn commands() -> Vec<Command> {
+        vec![
+            Command {
+                name: "integers",
+                args: "[file]",
+                run: |args| Day22::integers(cli::input(args)?),
+            },
+            Command {
+                name: "rocket",
+                args: "[chart]",
+                run: |args| Day22::rocket(cli::input(args)?),
+            },
+        ]
+    }
+}
+
+impl Day22 {
+    fn integers(text: String) -> Result<String, ApiError> {
         let lines = text
             .split('\n')
             .filter(|line| !line.is_empty())
             .map(|line| line.parse::<u64>())
             .collect::<Result<Vec<_>, _>>()
-            .map_err(|_| Status::BadRequest)?;
+            .map_err(|e| ApiError::BadRequest(format!("Could not parse integer: {}", e)))?;
 
         let result = lines.iter().fold(0u64, |acc, &x| acc ^ x);
         Ok("🎁".repeat(result as usize))
     }
-    fn rocket(chart: String) -> Result<String, Status> {
+    #[instrument(level = "debug", skip_all)]
+    fn rocket(chart: String) -> Result<String, ApiError> {
         let chart = Day22::parse_chart(chart)?;
         let graph = Day22::build_graph(&chart);
         let result = bfs(
@@ -38,7 +81,7 @@ impl Day22 {
             |&node| graph[node].clone(),
             |node| *node == graph.len() - 1,
         )
-        .expect("No path found");
+        .ok_or_else(|| ApiError::BadRequest("No path to the last star found".to_string()))?;
         let mut distance = 0.0;
 
         for i in 0..result.len() - 1 {
@@ -50,20 +93,18 @@ impl Day22 {
         Ok(format!("{} {:.3}", result.len() - 1, distance))
     }
 
-    fn parse_chart(chart: String) -> Result<(Vec<Coordinate>, Vec<Portal>), Status> {
+    fn parse_chart(chart: String) -> Result<(Vec<Coordinate>, Vec<Portal>), ApiError> {
         let lines: Vec<&str> = chart.split('\n').collect();
         let stars_amount = lines[0]
             .parse::<usize>()
-            .map_err(|_| {
-                println!("Could not parse starts amount");
-                Status::BadRequest
-            })
+            .map_err(|_| ApiError::BadRequest("Could not parse stars amount".to_string()))
             .and_then(|num| {
                 if (2..=100).contains(&num) {
                     Ok(num)
                 } else {
-                    println!("Stars amount is not in range 2..=100");
-                    Err(Status::BadRequest)
+                    Err(ApiError::BadRequest(
+                        "Stars amount is not in range 2..=100".to_string(),
+                    ))
                 }
             })?;
         let stars = lines
@@ -73,28 +114,30 @@ impl Day22 {
             .map(|coordinate_line| {
                 let numbers = coordinate_line.split(' ').collect::<Vec<&str>>();
                 if numbers.len() != 3 {
-                    return Err(Status::BadRequest);
+                    return Err(ApiError::BadRequest(
+                        "Star line does not have 3 numbers".to_string(),
+                    ));
                 }
                 numbers
                     .iter()
                     .map(|num| num.parse::<i32>())
                     .collect::<Result<Coordinate, _>>()
-                    .map_err(|_| Status::BadRequest)
+                    .map_err(|_| ApiError::BadRequest("Could not parse star".to_string()))
             })
             .collect::<Result<Vec<Coordinate>, _>>()?;
 
-        let portals_amount = lines[stars_amount + 1]
+        let portals_amount = lines
+            .get(stars_amount + 1)
+            .ok_or_else(|| ApiError::BadRequest("Portals amount is missing".to_string()))?
             .parse::<usize>()
-            .map_err(|_| {
-                println!("Could not parse portals amount");
-                Status::BadRequest
-            })
+            .map_err(|_| ApiError::BadRequest("Could not parse portals amount".to_string()))
             .and_then(|num| {
                 if (1..=100).contains(&num) {
                     Ok(num)
                 } else {
-                    println!("Portals amount is not in range 1..=100");
-                    Err(Status::BadRequest)
+                    Err(ApiError::BadRequest(
+                        "Portals amount is not in range 1..=100".to_string(),
+                    ))
                 }
             })?;
 
@@ -105,17 +148,15 @@ impl Day22 {
             .map(|portal_line| {
                 let numbers = portal_line.split(' ').collect::<Vec<&str>>();
                 if numbers.len() != 2 {
-                    println!("Portal line does not have 2 numbers");
-                    return Err(Status::BadRequest);
+                    return Err(ApiError::BadRequest(
+                        "Portal line does not have 2 numbers".to_string(),
+                    ));
                 }
                 numbers
                     .iter()
                     .map(|num| num.parse::<usize>())
                     .collect::<Result<Portal, _>>()
-                    .map_err(|_| {
-                        println!("Could not parse portal");
-                        Status::BadRequest
-                    })
+                    .map_err(|_| ApiError::BadRequest("Could not parse portal".to_string()))
             })
             .collect::<Result<Vec<Portal>, _>>()?;
 
@@ -123,7 +164,7 @@ impl Day22 {
     }
 
     fn build_graph(chart: &(Vec<Coordinate>, Vec<Portal>)) -> Graph {
-        println!("Chart Length: {}", chart.0.len());
+        trace!(stars = chart.0.len(), "Building graph");
         let mut graph = Graph::with_capacity(chart.0.len());
         for i in 0..chart.0.len() {
             let left_connections = chart
@@ -147,7 +188,7 @@ impl Day22 {
                     .collect::<Vec<CoordinateIndex>>(),
             );
         }
-        println!("Graph: {:?}", graph);
+        trace!(?graph, "Built graph");
         graph
     }
 }
@@ -1,98 +1,828 @@
-use rocket::serde::{json::Json, Deserialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
 
-use rocket::http::Status;
-use rocket::{post, routes, Route};
-use serde::Serialize;
+use handlebars::Handlebars;
+use rocket::data::{self, Data, FromData, Limits};
+use rocket::serde::json::{serde_json, Json, Value};
+use rocket::serde::Deserialize;
 
-#[derive(Deserialize)]
-struct Reindeer<'r> {
-    name: &'r str,
-    strength: i32,
+use rocket::{post, routes, Request, Route};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use crate::day::Day;
+use crate::error::{ApiError, FieldError};
+use crate::openapi::{Body, Operation};
+
+/// Names are borrowed from the body unless they contain escapes.
+#[derive(Deserialize, JsonSchema)]
+pub struct Reindeer<'r> {
+    #[serde(borrow)]
+    pub name: Cow<'r, str>,
+    pub strength: i32,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ContestParticipant<'r> {
+    #[serde(flatten, borrow)]
+    pub reindeer: Reindeer<'r>,
+    pub speed: f32,
+    pub height: i32,
+    pub antler_width: i32,
+    pub snow_magic_power: i32,
+    #[serde(borrow)]
+    pub favorite_food: Cow<'r, str>,
+    #[serde(rename = "cAnD13s_3ATeN-yesT3rdAy")]
+    pub candies_eaten_yesterday: i32,
+}
+
+/// The type a field of a participant must have, and the least integer it may take.
+#[derive(Clone, Copy)]
+enum Expected {
+    Text,
+    Integer {
+        min: i32,
+    },
+    /// A finite `f32`.
+    Number,
+}
+
+const REINDEER_FIELDS: [(&str, Expected); 2] = [
+    ("name", Expected::Text),
+    ("strength", Expected::Integer { min: i32::MIN }),
+];
+
+const PARTICIPANT_FIELDS: [(&str, Expected); 8] = [
+    ("name", Expected::Text),
+    ("strength", Expected::Integer { min: i32::MIN }),
+    ("speed", Expected::Number),
+    ("height", Expected::Integer { min: 0 }),
+    ("antler_width", Expected::Integer { min: i32::MIN }),
+    ("snow_magic_power", Expected::Integer { min: i32::MIN }),
+    ("favorite_food", Expected::Text),
+    (
+        "cAnD13s_3ATeN-yesT3rdAy",
+        Expected::Integer { min: i32::MIN },
+    ),
+];
+
+impl Expected {
+    /// Why `value` is not what is expected, if it is not.
+    fn reason(self, value: &Value) -> Option<String> {
+        match (self, value) {
+            (Expected::Text, Value::String(_)) => None,
+            (Expected::Text, value) => Some(format!("must be a string, not {}", kind(value))),
+            (Expected::Integer { min }, Value::Number(number)) => match number.as_i64() {
+                None => Some(format!("must be an integer, not {}", number)),
+                Some(n) if n > i64::from(i32::MAX) => Some(format!("must be at most {}", i32::MAX)),
+                Some(n) if n < i64::from(min) => Some(match min {
+                    0 => "must not be negative".to_string(),
+                    min => format!("must be at least {}", min),
+                }),
+                Some(_) => None,
+            },
+            (Expected::Integer { .. }, value) => {
+                Some(format!("must be an integer, not {}", kind(value)))
+            }
+            (Expected::Number, Value::Number(number))
+                if (number.as_f64().unwrap_or(f64::NAN) as f32).is_finite() =>
+            {
+                None
+            }
+            (Expected::Number, Value::Number(number)) => {
+                Some(format!("must be a finite number, not {}", number))
+            }
+            (Expected::Number, value) => Some(format!("must be a number, not {}", kind(value))),
+        }
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Adds why the `fields` of the element at `index` are invalid to `errors`.
+fn check_fields(
+    value: &Value,
+    index: Option<usize>,
+    fields: &[(&str, Expected)],
+    errors: &mut Vec<FieldError>,
+) {
+    let Value::Object(object) = value else {
+        errors.push(FieldError {
+            index,
+            field: None,
+            reason: format!("must be an object, not {}", kind(value)),
+        });
+        return;
+    };
+    for (field, expected) in fields {
+        let reason = match object.get(*field) {
+            None => Some("is missing".to_string()),
+            Some(value) => expected.reason(value),
+        };
+        if let Some(reason) = reason {
+            errors.push(FieldError {
+                index,
+                field: Some(field.to_string()),
+                reason,
+            });
+        }
+    }
+}
+
+/// Adds why the elements of `value` are invalid to `errors`, or why it is not an array; `field`
+/// names it unless it is the whole body.
+fn check_elements(
+    value: &Value,
+    field: Option<&str>,
+    fields: &[(&str, Expected)],
+    errors: &mut Vec<FieldError>,
+) {
+    match value {
+        Value::Array(elements) => {
+            for (index, element) in elements.iter().enumerate() {
+                check_fields(element, Some(index), fields, errors);
+            }
+        }
+        value => errors.push(FieldError {
+            index: None,
+            field: field.map(str::to_string),
+            reason: format!("must be an array, not {}", kind(value)),
+        }),
+    }
+}
+
+/// A body whose fields can be checked before it is deserialized, to report all invalid ones.
+pub trait Checked {
+    fn check(value: &Value) -> Vec<FieldError>;
+}
+
+impl Checked for Vec<Reindeer<'_>> {
+    fn check(value: &Value) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_elements(value, None, &REINDEER_FIELDS, &mut errors);
+        errors
+    }
+}
+
+/// A body of a participant alone, like the reindeer of the registry.
+#[cfg(feature = "reindeer")]
+pub fn check_participant(value: &Value) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    check_fields(value, None, &PARTICIPANT_FIELDS, &mut errors);
+    errors
+}
+
+/// Adds why the categories in `value` are invalid to `errors`, or why it is not an array.
+fn check_categories(value: &Value, errors: &mut Vec<FieldError>) {
+    let Value::Array(categories) = value else {
+        errors.push(FieldError {
+            index: None,
+            field: Some("categories".to_string()),
+            reason: format!("must be an array, not {}", kind(value)),
+        });
+        return;
+    };
+    for (index, category) in categories.iter().enumerate() {
+        if let Err(e) = Category::deserialize(category) {
+            errors.push(FieldError {
+                index: Some(index),
+                field: Some("categories".to_string()),
+                reason: e.to_string(),
+            });
+        }
+    }
+}
+
+/// A body of optional `categories` alone, like the contests held over the registry.
+#[cfg(feature = "reindeer")]
+pub fn check_contest_categories(value: &Value) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    match value {
+        Value::Object(object) => {
+            if let Some(categories) = object.get("categories") {
+                check_categories(categories, &mut errors);
+            }
+        }
+        value => errors.push(FieldError {
+            index: None,
+            field: None,
+            reason: format!("must be an object, not {}", kind(value)),
+        }),
+    }
+    errors
+}
+
+/// A JSON body deserialized once [`Checked`] found its fields valid, capped by the `json` limit.
+///
+/// Take it as `Result<Validated<T>, ApiError>`, so the invalid fields reach the response rather
+/// than a catcher, which would only get to see the status.
+pub struct Validated<T>(pub T);
+
+/// The body a [`Validated`] borrows from, which has to outlive the guard.
+struct CachedBody(Vec<u8>);
+
+#[rocket::async_trait]
+impl<'r, T: Checked + Deserialize<'r>> FromData<'r> for Validated<T> {
+    type Error = ApiError;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = request.limits().get("json").unwrap_or(Limits::JSON);
+        let body = match data.open(limit).into_bytes().await {
+            Ok(body) if body.is_complete() => body.into_inner(),
+            Ok(_) => {
+                return data::Outcome::Error(
+                    ApiError::PayloadTooLarge(format!(
+                        "Request body exceeds the json limit of {}",
+                        limit
+                    ))
+                    .reject(request),
+                )
+            }
+            Err(e) => {
+                return data::Outcome::Error(
+                    ApiError::BadRequest(format!("Could not read body: {}", e)).reject(request),
+                )
+            }
+        };
+        let body = &request.local_cache(|| CachedBody(body)).0;
+        let errors = match serde_json::from_slice::<Value>(body) {
+            Ok(value) => T::check(&value),
+            Err(e) => {
+                return data::Outcome::Error(
+                    ApiError::BadRequest(format!("Body is not valid JSON: {}", e)).reject(request),
+                )
+            }
+        };
+        if !errors.is_empty() {
+            return data::Outcome::Error(ApiError::Invalid(errors).reject(request));
+        }
+        match serde_json::from_slice(body) {
+            Ok(value) => data::Outcome::Success(Validated(value)),
+            Err(e) => data::Outcome::Error(
+                ApiError::UnprocessableEntity(format!("Body is invalid: {}", e)).reject(request),
+            ),
+        }
+    }
+}
+
+/// A numeric field of [`ContestParticipant`] a category ranks by.
+#[derive(
+    Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Strength,
+    Speed,
+    Height,
+    AntlerWidth,
+    SnowMagicPower,
+    CandiesEatenYesterday,
+}
+
+impl Field {
+    const ALL: [Field; 6] = [
+        Field::Strength,
+        Field::Speed,
+        Field::Height,
+        Field::AntlerWidth,
+        Field::SnowMagicPower,
+        Field::CandiesEatenYesterday,
+    ];
+
+    /// The name of the field, as in categories.
+    #[cfg(feature = "reindeer")]
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Strength => "strength",
+            Field::Speed => "speed",
+            Field::Height => "height",
+            Field::AntlerWidth => "antler_width",
+            Field::SnowMagicPower => "snow_magic_power",
+            Field::CandiesEatenYesterday => "candies_eaten_yesterday",
+        }
+    }
+
+    /// The value of the field, which all fit an `f64` exactly.
+    pub fn value(self, participant: &ContestParticipant<'_>) -> f64 {
+        match self {
+            Field::Strength => participant.reindeer.strength.into(),
+            Field::Speed => f64::from(participant.speed),
+            Field::Height => participant.height.into(),
+            Field::AntlerWidth => participant.antler_width.into(),
+            Field::SnowMagicPower => participant.snow_magic_power.into(),
+            Field::CandiesEatenYesterday => participant.candies_eaten_yesterday.into(),
+        }
+    }
+
+    /// The value as JSON, with integers rendered without a fraction.
+    fn json(self, participant: &ContestParticipant<'_>) -> Value {
+        match self {
+            Field::Speed => Value::from(self.display(self.value(participant))),
+            _ => Value::from(self.value(participant) as i64),
+        }
+    }
+
+    /// A value of the field rounded to the digits it was sent with, so a speed of 9.2 does not
+    /// show as the 9.199999809265137 it widens to.
+    fn display(self, value: f64) -> f64 {
+        const SPEED_DIGITS: i32 = 7;
+        match self {
+            Field::Speed if value != 0.0 && value.is_finite() => {
+                let scale = 10f64.powi(SPEED_DIGITS - 1 - value.abs().log10().floor() as i32);
+                (value * scale).round() / scale
+            }
+            _ => value,
+        }
+    }
+}
+
+/// Whether the highest or the lowest value wins a category.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    Max,
+    Min,
+}
+
+/// A category of the contest, whose winner is described by a handlebars `template`.
+///
+/// The template sees the fields of the winner by their snake case names, e.g. `{{name}}` or
+/// `{{favorite_food}}`, along with the `{{field}}` ranked by and its `{{value}}`.
+#[derive(Deserialize, JsonSchema)]
+pub struct Category {
+    pub name: String,
+    pub field: Field,
+    #[serde(default)]
+    pub direction: Direction,
+    pub template: Option<String>,
+}
+
+impl Category {
+    const TEMPLATE: &'static str = "{{name}} wins with a {{field}} of {{value}}";
+
+    fn new(name: &str, field: Field, template: &str) -> Self {
+        Category {
+            name: name.to_string(),
+            field,
+            direction: Direction::Max,
+            template: Some(template.to_string()),
+        }
+    }
+
+    /// The categories of the original contest.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Category::new(
+                "fastest",
+                Field::Speed,
+                "Speeding past the finish line with a strength of {{strength}} is {{name}}",
+            ),
+            Category::new(
+                "tallest",
+                Field::Height,
+                "{{name}} is standing tall with his {{antler_width}} cm wide antlers",
+            ),
+            Category::new(
+                "magician",
+                Field::SnowMagicPower,
+                "{{name}} could blast you away with a snow magic power of {{snow_magic_power}}",
+            ),
+            Category::new(
+                "consumer",
+                Field::CandiesEatenYesterday,
+                "{{name}} ate lots of candies, but also some {{favorite_food}}",
+            ),
+        ]
+    }
+
+    /// The winner of this category; of several with the same value, the last one.
+    fn winner<'a, 'r>(
+        &self,
+        participants: &'a [ContestParticipant<'r>],
+    ) -> Option<&'a ContestParticipant<'r>> {
+        let values = participants
+            .iter()
+            .map(|participant| (self.field.value(participant), participant));
+        match self.direction {
+            Direction::Max => values.max_by(|(a, _), (b, _)| a.total_cmp(b)),
+            Direction::Min => values.max_by(|(a, _), (b, _)| b.total_cmp(a)),
+        }
+        .map(|(_, participant)| participant)
+    }
+
+    /// Orders better values first, and the same values by name, then by their order in the
+    /// request.
+    fn compare(&self, a: &ContestParticipant<'_>, b: &ContestParticipant<'_>) -> Ordering {
+        let by_value = self.field.value(a).total_cmp(&self.field.value(b));
+        match self.direction {
+            Direction::Max => by_value.reverse(),
+            Direction::Min => by_value,
+        }
+        .then_with(|| a.reindeer.name.cmp(&b.reindeer.name))
+    }
+
+    /// The best `top` participants of this category, in the order of [`Category::compare`].
+    fn ranking(&self, participants: &[ContestParticipant<'_>], top: usize) -> Ranking {
+        let mut ranked = participants.iter().collect::<Vec<_>>();
+        ranked.sort_by(|a, b| self.compare(a, b));
+        let value = |index: usize| self.field.value(ranked[index]);
+        let mut rank = 1;
+        let entries = (0..ranked.len().min(top))
+            .map(|index| {
+                if index > 0 && value(index) != value(index - 1) {
+                    rank = index + 1;
+                }
+                RankingEntry {
+                    rank,
+                    name: ranked[index].reindeer.name.to_string(),
+                    value: self.field.json(ranked[index]),
+                    tied: (index > 0 && value(index) == value(index - 1))
+                        || (index + 1 < ranked.len() && value(index) == value(index + 1)),
+                }
+            })
+            .collect();
+        Ranking {
+            name: self.name.clone(),
+            field: self.field,
+            direction: self.direction,
+            entries,
+        }
+    }
+}
+
+/// The participants alone run the original contest, with categories a custom one.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum Contest<'r> {
+    Participants(#[serde(borrow)] Vec<ContestParticipant<'r>>),
+    Custom {
+        #[serde(borrow)]
+        participants: Vec<ContestParticipant<'r>>,
+        categories: Vec<Category>,
+    },
+}
+
+impl<'r> Contest<'r> {
+    /// The participants and the categories, which are not empty and have unique names.
+    fn into_parts(self) -> Result<(Vec<ContestParticipant<'r>>, Vec<Category>), ApiError> {
+        let (participants, categories) = match self {
+            Contest::Participants(participants) => (participants, Category::defaults()),
+            Contest::Custom {
+                participants,
+                categories,
+            } => (participants, categories),
+        };
+        Day4::check(&participants, &categories)?;
+        Ok((participants, categories))
+    }
+}
+
+/// Errors of participants are named by their field, those of categories as `categories`, both
+/// along with the `index` of the element.
+impl Checked for Contest<'_> {
+    fn check(value: &Value) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let Value::Object(object) = value else {
+            check_elements(value, None, &PARTICIPANT_FIELDS, &mut errors);
+            return errors;
+        };
+        for field in ["participants", "categories"] {
+            match object.get(field) {
+                Some(value) if field == "participants" => {
+                    check_elements(value, Some(field), &PARTICIPANT_FIELDS, &mut errors)
+                }
+                Some(value) => check_categories(value, &mut errors),
+                None => errors.push(FieldError {
+                    index: None,
+                    field: Some(field.to_string()),
+                    reason: "is missing".to_string(),
+                }),
+            }
+        }
+        errors
+    }
 }
 
-#[derive(Deserialize)]
-struct ContestParticipant<'r> {
-    #[serde(flatten)]
-    reindeer: Reindeer<'r>,
-    speed: f32,
+/// What a category template renders from.
+#[derive(Serialize)]
+struct Winner<'a> {
+    name: &'a str,
+    strength: i32,
+    speed: Value,
     height: i32,
     antler_width: i32,
     snow_magic_power: i32,
-    favorite_food: &'r str,
-    #[serde(rename = "cAnD13s_3ATeN-yesT3rdAy")]
+    favorite_food: &'a str,
     candies_eaten_yesterday: i32,
+    field: Field,
+    value: Value,
 }
 
-#[derive(Serialize)]
-struct ContestResults {
-    fastest: String,
-    tallest: String,
-    magician: String,
-    consumer: String,
+impl<'a> Winner<'a> {
+    fn new(participant: &'a ContestParticipant<'_>, field: Field) -> Self {
+        Winner {
+            name: &participant.reindeer.name,
+            strength: participant.reindeer.strength,
+            speed: Field::Speed.json(participant),
+            height: participant.height,
+            antler_width: participant.antler_width,
+            snow_magic_power: participant.snow_magic_power,
+            favorite_food: &participant.favorite_food,
+            candies_eaten_yesterday: participant.candies_eaten_yesterday,
+            field,
+            value: field.json(participant),
+        }
+    }
+}
+
+/// The message of every category by its name, in the order of the categories.
+struct ContestResults(Vec<(String, String)>);
+
+impl Serialize for ContestResults {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, message) in &self.0 {
+            map.serialize_entry(name, message)?;
+        }
+        map.end()
+    }
+}
+
+impl JsonSchema for ContestResults {
+    fn schema_name() -> String {
+        "ContestResults".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        BTreeMap::<String, String>::json_schema(gen)
+    }
+}
+
+/// The best participants of a category.
+#[derive(Serialize, JsonSchema)]
+struct Ranking {
+    name: String,
+    field: Field,
+    direction: Direction,
+    entries: Vec<RankingEntry>,
+}
+
+/// A participant of a [`Ranking`], whose `rank` is shared by all participants with the same
+/// value, which are `tied`.
+#[derive(Serialize, JsonSchema)]
+struct RankingEntry {
+    rank: usize,
+    name: String,
+    value: Value,
+    tied: bool,
+}
+
+/// Summary of the values of a field over all participants.
+///
+/// The deviation is that of the population, percentiles interpolate linearly between the two
+/// closest values. All of them are rounded to [`Stats::DECIMALS`] decimals.
+#[derive(Serialize, JsonSchema)]
+struct Stats {
+    min: f64,
+    max: f64,
+    mean: f64,
+    median: f64,
+    stddev: f64,
+    percentiles: BTreeMap<String, f64>,
+}
+
+impl Stats {
+    const PERCENTILES: [u8; 5] = [25, 75, 90, 95, 99];
+    /// Enough for the values sent, while a p99 between 8951 and 8952 shows as 8951.03 rather than
+    /// the 8951.029999999999 the interpolation gives.
+    const DECIMALS: i32 = 6;
+
+    fn round(field: Field, value: f64) -> f64 {
+        let scale = 10f64.powi(Self::DECIMALS);
+        (field.display(value) * scale).round() / scale
+    }
+
+    /// Summary of the `values` of `field`, which must not be empty.
+    fn new(field: Field, mut values: Vec<f64>) -> Self {
+        values.sort_by(f64::total_cmp);
+        let count = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let variance = values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        let percentile = |p: u8| {
+            let position = f64::from(p) / 100.0 * (values.len() - 1) as f64;
+            let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+            values[lower] + (values[upper] - values[lower]) * position.fract()
+        };
+        let round = |value| Self::round(field, value);
+        Stats {
+            min: round(values[0]),
+            max: round(values[values.len() - 1]),
+            mean: round(mean),
+            median: round(percentile(50)),
+            stddev: round(variance.sqrt()),
+            percentiles: Self::PERCENTILES
+                .into_iter()
+                .map(|p| (format!("p{}", p), round(percentile(p))))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+struct Rankings {
+    categories: Vec<Ranking>,
+    stats: BTreeMap<Field, Stats>,
 }
 
 #[post("/strength", data = "<reindeers>")]
-fn strength(reindeers: Json<Vec<Reindeer<'_>>>) -> String {
+fn strength(reindeers: Result<Validated<Vec<Reindeer<'_>>>, ApiError>) -> Result<String, ApiError> {
+    let Validated(reindeers) = reindeers?;
     reindeers
         .iter()
-        .map(|reindeer| reindeer.strength)
-        .sum::<i32>()
-        .to_string()
+        .try_fold(0i32, |sum, reindeer| sum.checked_add(reindeer.strength))
+        .map(|sum| sum.to_string())
+        .ok_or_else(|| {
+            ApiError::UnprocessableEntity("The total strength overflows i32".to_string())
+        })
 }
 
-#[post("/contest", data = "<contest_participants>")]
-fn contest<'r>(
-    contest_participants: Json<Vec<ContestParticipant<'r>>>,
-) -> Result<Json<ContestResults>, Status> {
-    let (fastest, tallest, magician, consumer) = (
-        contest_participants
-            .iter()
-            .max_by(|a, b| a.speed.total_cmp(&b.speed)),
-        contest_participants.iter().max_by_key(|&a| a.height),
-        contest_participants
-            .iter()
-            .max_by_key(|a| a.snow_magic_power),
-        contest_participants
-            .iter()
-            .max_by_key(|a| a.candies_eaten_yesterday),
-    );
+#[post("/contest", data = "<contest>")]
+fn contest(
+    contest: Result<Validated<Contest<'_>>, ApiError>,
+) -> Result<Json<ContestResults>, ApiError> {
+    let (participants, categories) = contest?.0.into_parts()?;
+    let results = Day4::winners(&participants, &categories)?
+        .into_iter()
+        .map(|win| (win.category.name.clone(), win.message))
+        .collect();
+    Ok(ContestResults(results).into())
+}
 
-    if [fastest, tallest, magician, consumer]
-        .iter()
-        .any(|x| x.is_none())
-    {
-        return Err(Status::BadRequest);
-    }
-    let (fastest, tallest, magician, consumer) = (
-        fastest.unwrap(),
-        tallest.unwrap(),
-        magician.unwrap(),
-        consumer.unwrap(),
-    );
-    Ok(ContestResults {
-        fastest: format!(
-            "Speeding past the finish line with a strength of {} is {}",
-            fastest.reindeer.strength, fastest.reindeer.name,
-        ),
-        tallest: format!(
-            "{} is standing tall with his {} cm wide antlers",
-            tallest.reindeer.name, tallest.antler_width
-        ),
-        magician: format!(
-            "{} could blast you away with a snow magic power of {}",
-            magician.reindeer.name, magician.snow_magic_power
-        ),
-        consumer: format!(
-            "{} ate lots of candies, but also some {}",
-            consumer.reindeer.name, consumer.favorite_food,
-        ),
+/// Unlike the winners of `contest`, ties are broken by name, see [`Category::compare`].
+#[post("/contest/rankings?<top>", data = "<contest>")]
+fn rankings(
+    top: Option<&str>,
+    contest: Result<Validated<Contest<'_>>, ApiError>,
+) -> Result<Json<Rankings>, ApiError> {
+    let top = match top.map(str::parse::<usize>) {
+        None => 3,
+        Some(Ok(top)) if top > 0 => top,
+        Some(_) => {
+            return Err(ApiError::BadRequest(format!(
+                "The top must be a number of at least 1, not {}",
+                top.unwrap_or_default()
+            )))
+        }
+    };
+    let (participants, categories) = contest?.0.into_parts()?;
+    Ok(Rankings {
+        categories: categories
+            .iter()
+            .map(|category| category.ranking(&participants, top))
+            .collect(),
+        stats: Field::ALL
+            .into_iter()
+            .map(|field| {
+                let values = participants
+                    .iter()
+                    .map(|participant| field.value(participant))
+                    .collect();
+                (field, Stats::new(field, values))
+            })
+            .collect(),
     }
     .into())
 }
 pub struct Day4 {}
 
+impl Day for Day4 {
+    const NAME: &'static str = "day4";
+    const PATH: &'static str = "/4";
+    const DESCRIPTION: &'static str = "Reindeer strength and contest results from JSON";
+
+    fn routes() -> Vec<Route> {
+        routes![strength, contest, rankings]
+    }
+
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("strength", "Sum of the strength of all reindeer")
+                .request(Body::json::<Vec<Reindeer>>())
+                .response(Body::Text),
+            Operation::new(
+                "contest",
+                "Winners of the reindeer contest; along with `categories` of which field to rank \
+                by, in which `direction` and a handlebars `template` for the message, winners of \
+                a custom contest",
+            )
+            .request(Body::json::<Contest>())
+            .response(Body::json::<ContestResults>()),
+            Operation::new(
+                "rankings",
+                "The `top` participants of every category of the contest, 3 by default, with ties \
+                sharing a rank and broken by name; along with statistics of every numeric field",
+            )
+            .parameter::<usize>("top")
+            .request(Body::json::<Contest>())
+            .response(Body::json::<Rankings>()),
+        ]
+    }
+}
+
+/// The winner of a category, described by its template.
+pub struct Win<'a> {
+    pub category: &'a Category,
+    #[cfg(feature = "reindeer")]
+    pub winner: &'a ContestParticipant<'a>,
+    pub message: String,
+}
+
 impl Day4 {
-    pub fn routes() -> Vec<Route> {
-        routes![strength, contest]
+    /// Checks that there are participants and categories, with unique names.
+    pub fn check(
+        participants: &[ContestParticipant<'_>],
+        categories: &[Category],
+    ) -> Result<(), ApiError> {
+        if participants.is_empty() {
+            return Err(ApiError::BadRequest(
+                "At least one contest participant is required".to_string(),
+            ));
+        }
+        if categories.is_empty() {
+            return Err(ApiError::BadRequest(
+                "At least one category is required".to_string(),
+            ));
+        }
+        let mut names = HashSet::new();
+        if let Some(category) = categories
+            .iter()
+            .find(|category| !names.insert(&category.name))
+        {
+            return Err(ApiError::BadRequest(format!(
+                "Category {} is given more than once",
+                category.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// The winner of every category, in the order of the categories.
+    pub fn winners<'a, 'r>(
+        participants: &'a [ContestParticipant<'r>],
+        categories: &'a [Category],
+    ) -> Result<Vec<Win<'a>>, ApiError> {
+        let mut templates = Handlebars::new();
+        templates.set_strict_mode(true);
+        templates.register_escape_fn(handlebars::no_escape);
+        for category in categories {
+            let template = category.template.as_deref().unwrap_or(Category::TEMPLATE);
+            templates
+                .register_template_string(&category.name, template)
+                .map_err(|e| {
+                    ApiError::BadRequest(format!(
+                        "Invalid template of category {}: {}",
+                        category.name, e
+                    ))
+                })?;
+        }
+        let mut results = Vec::with_capacity(categories.len());
+        for category in categories {
+            let Some(winner) = category.winner(participants) else {
+                continue;
+            };
+            let message = templates
+                .render(&category.name, &Winner::new(winner, category.field))
+                .map_err(|e| {
+                    ApiError::BadRequest(format!(
+                        "Could not render template of category {}: {}",
+                        category.name, e
+                    ))
+                })?;
+            results.push(Win {
+                category,
+                #[cfg(feature = "reindeer")]
+                winner,
+                message,
+            });
+        }
+        Ok(results)
     }
 }
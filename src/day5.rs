@@ -3,6 +3,9 @@ use rocket::serde::json::Json;
 use rocket::{post, routes};
 use rocket::{response, Request, Route};
 
+use crate::day::Day;
+use crate::openapi::{Body, Operation};
+
 enum SliceResult<'r> {
     Unsplit(Json<Vec<&'r str>>),
     Split(Json<Vec<Vec<&'r str>>>),
@@ -23,7 +26,7 @@ fn slice(
     limit: Option<usize>,
     split: Option<usize>,
     data: Json<Vec<&str>>,
-) -> SliceResult {
+) -> SliceResult<'_> {
     let offset = offset.unwrap_or(0);
     let limit = limit.unwrap_or(data.0.len());
     let take = data
@@ -44,8 +47,24 @@ fn slice(
 }
 pub struct Day5 {}
 
-impl Day5 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day5 {
+    const NAME: &'static str = "day5";
+    const PATH: &'static str = "/5";
+    const DESCRIPTION: &'static str = "Slicing and splitting lists of names";
+
+    fn routes() -> Vec<Route> {
         routes![slice]
     }
+
+    fn docs() -> Vec<Operation> {
+        vec![Operation::new(
+            "slice",
+            "Page of the names, split into lists of `split` names if given",
+        )
+        .parameter::<usize>("offset")
+        .parameter::<usize>("limit")
+        .parameter::<usize>("split")
+        .request(Body::json::<Vec<String>>())
+        .response(Body::json::<Vec<String>>())]
+    }
 }
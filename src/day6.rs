@@ -1,8 +1,13 @@
 use rocket::serde::json::Json;
 use rocket::serde::Serialize;
 use rocket::{post, routes, Route};
+use schemars::JsonSchema;
 
-#[derive(Serialize, Debug)]
+use crate::day::Day;
+use crate::limits::Text;
+use crate::openapi::{Body, Operation};
+
+#[derive(Serialize, JsonSchema, Debug)]
 struct ElfCount {
     elf: usize,
     #[serde(rename = "elf on a shelf")]
@@ -11,7 +16,8 @@ struct ElfCount {
     shelf_with_no_elf_on_it: usize,
 }
 #[post("/", data = "<text>")]
-fn count_elfs(text: &'_ str) -> Json<ElfCount> {
+fn count_elfs(text: Text) -> Json<ElfCount> {
+    let text = text.0;
     let elf_on_a_shelf =
         text.matches("elf on a shelf").count() + text.matches("shelf on a shelf").count();
     ElfCount {
@@ -23,8 +29,20 @@ fn count_elfs(text: &'_ str) -> Json<ElfCount> {
 }
 pub struct Day6 {}
 
-impl Day6 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day6 {
+    const NAME: &'static str = "day6";
+    const PATH: &'static str = "/6";
+    const DESCRIPTION: &'static str = "Counting elves and shelves in text";
+
+    fn routes() -> Vec<Route> {
         routes![count_elfs]
     }
+
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("count_elfs", "Elves and shelves mentioned in the text")
+                .request(Body::Text)
+                .response(Body::json::<ElfCount>()),
+        ]
+    }
 }
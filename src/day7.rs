@@ -1,23 +1,23 @@
 use base64::{engine::general_purpose, Engine};
-use rocket::http::{CookieJar, Status};
+use rocket::http::CookieJar;
 use rocket::serde::json::Json;
 use rocket::serde::{json, Serialize};
 use rocket::{get, routes, Route};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::collections::HashMap;
+use tracing::debug;
+
+use crate::day::Day;
+use crate::error::ApiError;
+use crate::openapi::{Body, Operation};
 
 #[get("/decode")]
-pub fn decode(cookies: &CookieJar<'_>) -> Result<String, Status> {
-    match cookies.get("recipe") {
-        None => Err(Status::BadRequest),
-        Some(recipe) => general_purpose::STANDARD
-            .decode(recipe.value())
-            .map(|it| String::from_utf8(it).unwrap())
-            .map_err(|_| Status::BadRequest),
-    }
+pub fn decode(cookies: &CookieJar<'_>) -> Result<String, ApiError> {
+    Day7::decode_recipe(cookies)
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug)]
 struct Ingredients {
     #[serde(flatten)]
     extra: HashMap<String, i64>,
@@ -28,32 +28,19 @@ struct Request {
     pantry: Ingredients,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug)]
+#[schemars(rename = "BakeResult")]
 struct Response {
     cookies: i64,
     pantry: Ingredients,
 }
 
 #[get("/bake")]
-fn bake(cookies: &CookieJar<'_>) -> Result<Json<Response>, Status> {
-    let request = match cookies.get("recipe") {
-        None => return Err(Status::BadRequest),
-        Some(recipe) => general_purpose::STANDARD
-            .decode(recipe.value())
-            .map(|it| String::from_utf8(it).unwrap())
-            .map_err(|_| Status::BadRequest),
-    };
-
-    let request = match request {
-        Err(_) => return Err(Status::BadRequest),
-        Ok(recipe) => recipe,
-    };
-
-    let request: Request = match json::from_str(&request) {
-        Err(_) => return Err(Status::BadRequest),
-        Ok(recipe) => recipe,
-    };
-    println!("request: {:?}", request);
+fn bake(cookies: &CookieJar<'_>) -> Result<Json<Response>, ApiError> {
+    let request = Day7::decode_recipe(cookies)?;
+    let request: Request = json::from_str(&request)
+        .map_err(|err| ApiError::BadRequest(format!("Recipe is not a valid request: {}", err)))?;
+    debug!(?request, "Baking cookies");
 
     let makeable_cookies_per_ingredient = request
         .recipe
@@ -91,8 +78,37 @@ fn bake(cookies: &CookieJar<'_>) -> Result<Json<Response>, Status> {
 
 pub struct Day7 {}
 
-impl Day7 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day7 {
+    const NAME: &'static str = "day7";
+    const PATH: &'static str = "/7";
+    const DESCRIPTION: &'static str = "Decoding recipe cookies and baking from a pantry";
+
+    fn routes() -> Vec<Route> {
         routes![decode, bake]
     }
+
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("decode", "Recipe from the base64 encoded `recipe` cookie")
+                .response(Body::Text),
+            Operation::new(
+                "bake",
+                "Cookies baked from the recipe and pantry in the `recipe` cookie",
+            )
+            .response(Body::json::<Response>()),
+        ]
+    }
+}
+
+impl Day7 {
+    fn decode_recipe(cookies: &CookieJar<'_>) -> Result<String, ApiError> {
+        let recipe = cookies
+            .get("recipe")
+            .ok_or_else(|| ApiError::BadRequest("Cookie recipe is missing".to_string()))?;
+        let bytes = general_purpose::STANDARD
+            .decode(recipe.value())
+            .map_err(|_| ApiError::BadRequest("Cookie recipe is not valid base64".to_string()))?;
+        String::from_utf8(bytes)
+            .map_err(|_| ApiError::BadRequest("Cookie recipe is not valid UTF-8".to_string()))
+    }
 }
@@ -1,8 +1,16 @@
-use reqwest::StatusCode;
-use rocket::http::Status;
 use rocket::serde::Serialize;
-use rocket::{get, routes, Route};
+use std::time::Instant;
+
+use reqwest::Client;
+use rocket::{get, routes, Route, State};
 use serde::Deserialize;
+use tracing::{debug, instrument};
+
+use crate::day::Day;
+use crate::error::ApiError;
+use crate::metrics::Metrics;
+use crate::openapi::{Body, Operation};
+use crate::telemetry::{RequestId, REQUEST_ID_HEADER};
 
 #[derive(Deserialize, Serialize, Debug)]
 struct PokeApiResponse {
@@ -12,40 +20,63 @@ struct PokeApiResponse {
 
 const EARTH_GRAVITY: f64 = 9.825;
 
-async fn get_pokedex_entry(pokedex_number: i32) -> Result<PokeApiResponse, Status> {
-    let response = reqwest::get(format!(
-        "https://pokeapi.co/api/v2/pokemon/{}/",
-        pokedex_number
-    ))
-    .await;
-    if response.is_err() {
-        let err = response.unwrap_err();
-        return match err.status() {
-            Some(StatusCode::NOT_FOUND) => Err(Status::NotFound),
-            _ => {
-                eprint!("Request failed: {}", err);
-                Err(Status::InternalServerError)
-            }
-        };
+#[instrument(skip(client, request_id, metrics), fields(request_id = request_id.as_str()))]
+async fn get_pokedex_entry(
+    client: &Client,
+    pokedex_number: i32,
+    request_id: &RequestId,
+    metrics: &Metrics,
+) -> Result<PokeApiResponse, ApiError> {
+    let upstream = |source| ApiError::Upstream {
+        context: "Could not fetch Pokédex entry",
+        source,
+    };
+    let start = Instant::now();
+    let response = client
+        .get(format!(
+            "https://pokeapi.co/api/v2/pokemon/{}/",
+            pokedex_number
+        ))
+        .header(REQUEST_ID_HEADER, request_id.as_str())
+        .send()
+        .await;
+    let outcome = match &response {
+        Ok(response) => response.status().as_u16().to_string(),
+        Err(_) => "error".to_string(),
     };
-    Ok(response.unwrap().json::<PokeApiResponse>().await.unwrap())
+    metrics.observe_pokeapi(&outcome, start.elapsed());
+    response
+        .and_then(|response| response.error_for_status())
+        .map_err(upstream)?
+        .json::<PokeApiResponse>()
+        .await
+        .map_err(upstream)
 }
 
 #[get("/weight/<pokedex_number>")]
-pub async fn get_weight(pokedex_number: i32) -> Result<String, Status> {
-    println!("get_weight({})", pokedex_number);
-    let weight = (get_pokedex_entry(pokedex_number)
+pub async fn get_weight(
+    pokedex_number: i32,
+    request_id: RequestId,
+    client: &State<Client>,
+    metrics: &State<Metrics>,
+) -> Result<String, ApiError> {
+    let weight = (get_pokedex_entry(client, pokedex_number, &request_id, metrics)
         .await?
         .weight_in_hectograms as f64
         / 10.0)
         .to_string();
-    println!("weight: {}", weight);
+    debug!(pokedex_number, weight, "Weighed Pokémon");
     Ok(weight)
 }
 
 #[get("/drop/<pokedex_number>")]
-pub async fn get_drop_momentum(pokedex_number: i32) -> Result<String, Status> {
-    let weight_in_kg = get_pokedex_entry(pokedex_number)
+pub async fn get_drop_momentum(
+    pokedex_number: i32,
+    request_id: RequestId,
+    client: &State<Client>,
+    metrics: &State<Metrics>,
+) -> Result<String, ApiError> {
+    let weight_in_kg = get_pokedex_entry(client, pokedex_number, &request_id, metrics)
         .await?
         .weight_in_hectograms as f64
         / 10.0;
@@ -56,8 +87,26 @@ pub async fn get_drop_momentum(pokedex_number: i32) -> Result<String, Status> {
 
 pub struct Day8 {}
 
-impl Day8 {
-    pub fn routes() -> Vec<Route> {
+impl Day for Day8 {
+    const NAME: &'static str = "day8";
+    const PATH: &'static str = "/8";
+    const DESCRIPTION: &'static str = "Pokémon weights and momentum from the PokéAPI";
+
+    fn routes() -> Vec<Route> {
         routes![get_weight, get_drop_momentum]
     }
+
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("get_weight", "Weight of the Pokémon in kilograms")
+                .parameter::<i32>("pokedex_number")
+                .response(Body::Text),
+            Operation::new(
+                "get_drop_momentum",
+                "Momentum of the Pokémon after falling ten meters",
+            )
+            .parameter::<i32>("pokedex_number")
+            .response(Body::Text),
+        ]
+    }
 }
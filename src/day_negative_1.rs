@@ -1,5 +1,7 @@
-use rocket::{get, Route, routes};
 use rocket::http::Status;
+use rocket::{get, routes, Route};
+
+use crate::day::Day;
 
 #[get("/error")]
 fn fake_error() -> Status {
@@ -8,8 +10,12 @@ fn fake_error() -> Status {
 
 pub struct DayNegative1 {}
 
-impl DayNegative1 {
-    pub fn routes() -> Vec<Route> {
+impl Day for DayNegative1 {
+    const NAME: &'static str = "day_negative_1";
+    const PATH: &'static str = "/-1";
+    const DESCRIPTION: &'static str = "Error status codes";
+
+    fn routes() -> Vec<Route> {
         routes![fake_error]
     }
-}
\ No newline at end of file
+}
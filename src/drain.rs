@@ -0,0 +1,246 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::figment::value::magic::RelativePathBuf;
+#[cfg(feature = "drain")]
+use rocket::{
+    http::Status,
+    request::{self, FromRequest},
+    Request,
+};
+use rocket::{Build, Config, Orbit, Rocket};
+use tokio::sync::Notify;
+use tokio::time::timeout;
+use tracing::{error, info, warn};
+
+#[cfg(feature = "drain")]
+use crate::error::ApiError;
+
+struct State {
+    /// Set once shutdown is requested; no new work gets admitted from then on.
+    draining: AtomicBool,
+    /// Set once the grace period is over; work still running aborts at its next checkpoint.
+    cancelled: AtomicBool,
+    jobs: AtomicUsize,
+    idle: Notify,
+    /// Where uploads and unpacked archives go, removed as a whole on shutdown.
+    scratch: PathBuf,
+}
+
+/// Coordinates the work that has to finish or be cancelled before the server shuts down.
+#[derive(Clone)]
+pub struct Drain(Arc<State>);
+
+impl Drain {
+    fn new(scratch: PathBuf) -> Self {
+        Drain(Arc::new(State {
+            draining: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+            jobs: AtomicUsize::new(0),
+            idle: Notify::new(),
+            scratch,
+        }))
+    }
+
+    pub fn scratch(&self) -> &Path {
+        &self.0.scratch
+    }
+
+    #[cfg(feature = "drain")]
+    fn draining(&self) -> bool {
+        self.0.draining.load(Ordering::SeqCst)
+    }
+
+    #[cfg(feature = "drain")]
+    fn admit(&self) -> Result<(), ApiError> {
+        if self.draining() {
+            return Err(ApiError::Unavailable("Server is shutting down".to_string()));
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "jobs")]
+    fn cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn start(&self) -> Job {
+        self.0.jobs.fetch_add(1, Ordering::SeqCst);
+        Job(self.clone())
+    }
+
+    #[cfg(feature = "jobs")]
+    fn finish(&self) {
+        if self.0.jobs.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+
+    fn jobs(&self) -> usize {
+        self.0.jobs.load(Ordering::SeqCst)
+    }
+
+    async fn idle(&self) {
+        loop {
+            // Registered before checking, so a job finishing in between is not missed
+            let idle = self.0.idle.notified();
+            if self.jobs() == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    fn clean(&self) {
+        match std::fs::remove_dir_all(self.scratch()) {
+            Ok(()) => info!(scratch = ?self.scratch(), "Removed scratch directory"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                error!(scratch = ?self.scratch(), "Could not remove scratch directory: {}", e)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "drain")]
+fn drain<'r>(request: &'r Request<'_>) -> Result<&'r Drain, (Status, ApiError)> {
+    request.rocket().state::<Drain>().ok_or_else(|| {
+        (
+            Status::InternalServerError,
+            ApiError::internal("Could not admit request", "Drain is not managed"),
+        )
+    })
+}
+
+/// Admits a request only while the server is not shutting down, answering 503 otherwise.
+///
+/// For connections like websockets that end on [`rocket::Shutdown`] themselves.
+#[cfg(feature = "day19")]
+pub struct Accepting;
+
+#[cfg(feature = "day19")]
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Accepting {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match drain(request).and_then(|drain| drain.admit().map_err(|e| e.reject(request))) {
+            Ok(()) => request::Outcome::Success(Accepting),
+            Err(e) => request::Outcome::Error(e),
+        }
+    }
+}
+
+/// Work that shutdown waits for, like unpacking an archive, admitted like [`Accepting`].
+///
+/// Long running work calls [`Job::checkpoint`] regularly to stop once the grace period is over.
+#[cfg(feature = "jobs")]
+pub struct Job(Drain);
+
+#[cfg(feature = "jobs")]
+impl Job {
+    /// Work outside of any request, like from the command line, that is never cancelled.
+    #[cfg(feature = "day20")]
+    pub fn detached() -> Job {
+        Drain::new(std::env::temp_dir()).start()
+    }
+
+    #[cfg(feature = "day20")]
+    pub fn scratch(&self) -> &Path {
+        self.0.scratch()
+    }
+
+    /// Fails with 503 if the work has to be cancelled because the server is shutting down.
+    pub fn checkpoint(&self) -> Result<(), ApiError> {
+        if self.0.cancelled() {
+            return Err(ApiError::Unavailable(
+                "Cancelled because the server is shutting down".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "jobs")]
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+#[cfg(feature = "jobs")]
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Job {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let drain = match drain(request) {
+            Ok(drain) => drain,
+            Err(e) => return request::Outcome::Error(e),
+        };
+        let job = drain.start();
+        // Checked after counting the job, so shutdown either waits for it or it gets refused
+        match drain.admit() {
+            Ok(()) => request::Outcome::Success(job),
+            Err(e) => request::Outcome::Error(e.reject(request)),
+        }
+    }
+}
+
+/// Drains the server once shutdown is requested.
+///
+/// New [`Accepting`] connections and [`Job`]s get refused right away, while rocket stops
+/// accepting connections at all. Jobs still running after the grace period of the `shutdown`
+/// config get cancelled, and the scratch directory is removed once they stopped or the mercy
+/// period is over, too.
+pub struct GracefulShutdown;
+
+#[rocket::async_trait]
+impl Fairing for GracefulShutdown {
+    fn info(&self) -> Info {
+        Info {
+            name: "Graceful shutdown",
+            kind: Kind::Ignite | Kind::Shutdown,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let temp_dir = rocket
+            .figment()
+            .extract_inner::<RelativePathBuf>(Config::TEMP_DIR)
+            .map(|temp_dir| temp_dir.relative())
+            .unwrap_or_else(|_| std::env::temp_dir());
+        let scratch = temp_dir.join(format!("cch23-{}", std::process::id()));
+        if let Err(e) = std::fs::create_dir_all(&scratch) {
+            error!(?scratch, "Could not create scratch directory: {}", e);
+            return Err(rocket);
+        }
+        Ok(rocket.manage(Drain::new(scratch)))
+    }
+
+    async fn on_shutdown(&self, rocket: &Rocket<Orbit>) {
+        let Some(drain) = rocket.state::<Drain>() else {
+            return;
+        };
+        let config = &rocket.config().shutdown;
+        drain.0.draining.store(true, Ordering::SeqCst);
+        info!(jobs = drain.jobs(), "Draining");
+        let grace = Duration::from_secs(config.grace.into());
+        if timeout(grace, drain.idle()).await.is_err() {
+            warn!(
+                jobs = drain.jobs(),
+                "Cancelling the jobs still running after the grace period"
+            );
+            drain.0.cancelled.store(true, Ordering::SeqCst);
+            let mercy = Duration::from_secs(config.mercy.into());
+            if timeout(mercy, drain.idle()).await.is_err() {
+                warn!(jobs = drain.jobs(), "Jobs did not stop when cancelled");
+            }
+        }
+        drain.clean();
+    }
+}
@@ -0,0 +1,337 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+
+use rocket::http::{ContentType, Status};
+use rocket::response::Responder;
+use rocket::serde::json::serde_json;
+use rocket::serde::Serialize;
+use rocket::{catch, catchers, response, Catcher, Request, Response};
+use schemars::JsonSchema;
+use tracing::error;
+
+use crate::auth;
+use crate::telemetry::RequestId;
+
+/// Error shared by all day modules, rendered as an RFC 7807 `application/problem+json` body.
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    UnprocessableEntity(String),
+    /// The fields of the request body that are missing, of the wrong type or out of range.
+    Invalid(Vec<FieldError>),
+    PayloadTooLarge(String),
+    TooManyRequests(String),
+    Unavailable(String),
+    #[cfg(feature = "upstream")]
+    Upstream {
+        context: &'static str,
+        source: reqwest::Error,
+    },
+    #[cfg(feature = "database")]
+    Database {
+        context: &'static str,
+        source: sqlx::Error,
+    },
+    Internal {
+        context: &'static str,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    Status(Status),
+}
+
+/// RFC 7807 problem details.
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'static str,
+    status: u16,
+    code: String,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<Vec<FieldError>>,
+    instance: String,
+}
+
+/// Why a field of the request body is invalid, with the `index` of its element if the body is
+/// an array; without a `field` the element itself is.
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct FieldError {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub reason: String,
+}
+
+impl ApiError {
+    pub fn internal(
+        context: &'static str,
+        source: impl Into<Box<dyn Error + Send + Sync>>,
+    ) -> Self {
+        ApiError::Internal {
+            context,
+            source: source.into(),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::BadRequest(_) => Status::BadRequest,
+            ApiError::Unauthorized(_) => Status::Unauthorized,
+            ApiError::Forbidden(_) => Status::Forbidden,
+            ApiError::NotFound(_) => Status::NotFound,
+            ApiError::UnprocessableEntity(_) | ApiError::Invalid(_) => Status::UnprocessableEntity,
+            ApiError::PayloadTooLarge(_) => Status::PayloadTooLarge,
+            ApiError::TooManyRequests(_) => Status::TooManyRequests,
+            ApiError::Unavailable(_) => Status::ServiceUnavailable,
+            #[cfg(feature = "upstream")]
+            ApiError::Upstream { source, .. } => match source.status() {
+                Some(status) if status.as_u16() == 404 => Status::NotFound,
+                _ => Status::BadGateway,
+            },
+            #[cfg(feature = "database")]
+            ApiError::Database { .. } => Status::InternalServerError,
+            ApiError::Internal { .. } => Status::InternalServerError,
+            ApiError::Status(status) => *status,
+        }
+    }
+
+    pub fn code(&self) -> String {
+        match self {
+            ApiError::BadRequest(_) => "bad_request".to_string(),
+            ApiError::Unauthorized(_) => "unauthorized".to_string(),
+            ApiError::Forbidden(_) => "forbidden".to_string(),
+            ApiError::NotFound(_) => "not_found".to_string(),
+            ApiError::UnprocessableEntity(_) => "unprocessable_entity".to_string(),
+            ApiError::Invalid(_) => "invalid_fields".to_string(),
+            ApiError::PayloadTooLarge(_) => "payload_too_large".to_string(),
+            ApiError::TooManyRequests(_) => "too_many_requests".to_string(),
+            ApiError::Unavailable(_) => "service_unavailable".to_string(),
+            #[cfg(feature = "upstream")]
+            ApiError::Upstream { .. } => "upstream_error".to_string(),
+            #[cfg(feature = "database")]
+            ApiError::Database { .. } => "database_error".to_string(),
+            ApiError::Internal { .. } => "internal_error".to_string(),
+            ApiError::Status(status) => status
+                .reason_lossy()
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::UnprocessableEntity(message)
+            | ApiError::PayloadTooLarge(message)
+            | ApiError::TooManyRequests(message)
+            | ApiError::Unavailable(message) => message.clone(),
+            ApiError::Invalid(errors) => match errors.len() {
+                1 => "1 field of the request body is invalid".to_string(),
+                n => format!("{} fields of the request body are invalid", n),
+            },
+            #[cfg(feature = "upstream")]
+            ApiError::Upstream { context, .. } => context.to_string(),
+            #[cfg(feature = "database")]
+            ApiError::Database { context, .. } => context.to_string(),
+            ApiError::Internal { context, .. } => context.to_string(),
+            ApiError::Status(status) => status.reason_lossy().to_string(),
+        }
+    }
+
+    /// Fails a guard with this error, remembering it for the catcher of its status, which
+    /// otherwise only gets to see the status.
+    pub fn reject(self, request: &Request<'_>) -> (Status, ApiError) {
+        let status = self.status();
+        let message = self.message();
+        request.local_cache(|| Rejection(Some((status, message))));
+        (status, self)
+    }
+
+    fn context(&self) -> Option<String> {
+        match self {
+            #[cfg(feature = "upstream")]
+            ApiError::Upstream { source, .. } => Some(source.to_string()),
+            #[cfg(feature = "database")]
+            ApiError::Database { source, .. } => Some(source.to_string()),
+            ApiError::Internal { source, .. } => Some(source.to_string()),
+            _ => None,
+        }
+    }
+
+    /// What of the source clients get to see: the failure of an upstream API, but nothing of the
+    /// database or of internal errors, whose sources are only logged.
+    fn public_context(&self) -> Option<String> {
+        match self {
+            #[cfg(feature = "database")]
+            ApiError::Database { .. } => None,
+            ApiError::Internal { .. } => None,
+            _ => self.context(),
+        }
+    }
+
+    fn problem(&self, request: &Request<'_>) -> Problem {
+        let status = self.status();
+        Problem {
+            problem_type: "about:blank",
+            title: status.reason_lossy(),
+            status: status.code,
+            code: self.code(),
+            detail: self.message(),
+            context: self.public_context(),
+            errors: match self {
+                ApiError::Invalid(errors) => Some(errors.clone()),
+                _ => None,
+            },
+            instance: request.uri().to_string(),
+        }
+    }
+}
+
+/// The error a guard of the request failed with, see [`ApiError::reject`].
+struct Rejection(Option<(Status, String)>);
+
+fn rejection(request: &Request<'_>, status: Status) -> Option<String> {
+    match &request.local_cache(|| Rejection(None)).0 {
+        Some((rejected, message)) if *rejected == status => Some(message.clone()),
+        _ => None,
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.context() {
+            Some(context) => write!(f, "{}: {}", self.message(), context),
+            None => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl Error for ApiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "upstream")]
+            ApiError::Upstream { source, .. } => Some(source),
+            #[cfg(feature = "database")]
+            ApiError::Database { source, .. } => Some(source),
+            ApiError::Internal { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+        if status.class().is_server_error() {
+            error!(
+                request_id = RequestId::of(request),
+                code = self.code(),
+                "{} {}: {}",
+                request.method(),
+                request.uri(),
+                self
+            );
+        }
+        let body = serde_json::to_string(&self.problem(request)).map_err(|err| {
+            error!("Could not serialize problem: {}", err);
+            Status::InternalServerError
+        })?;
+        let mut response = Response::build();
+        response
+            .status(status)
+            .header(ContentType::new("application", "problem+json"))
+            .sized_body(body.len(), Cursor::new(body));
+        if status == Status::Unauthorized {
+            response.raw_header("WWW-Authenticate", auth::CHALLENGE);
+        }
+        response.ok()
+    }
+}
+
+#[catch(404)]
+fn not_found(request: &Request) -> ApiError {
+    ApiError::NotFound(format!(
+        "No route matches {} {}",
+        request.method(),
+        request.uri()
+    ))
+}
+
+#[catch(400)]
+fn bad_request(request: &Request) -> ApiError {
+    ApiError::BadRequest(
+        rejection(request, Status::BadRequest).unwrap_or_else(|| "Bad Request".to_string()),
+    )
+}
+
+#[catch(401)]
+fn unauthorized(request: &Request) -> ApiError {
+    ApiError::Unauthorized(
+        rejection(request, Status::Unauthorized)
+            .unwrap_or_else(|| "This route requires an API key".to_string()),
+    )
+}
+
+#[catch(403)]
+fn forbidden(request: &Request) -> ApiError {
+    ApiError::Forbidden(
+        rejection(request, Status::Forbidden)
+            .unwrap_or_else(|| "The API key may not access this route".to_string()),
+    )
+}
+
+#[catch(503)]
+fn service_unavailable(request: &Request) -> ApiError {
+    ApiError::Unavailable(
+        rejection(request, Status::ServiceUnavailable)
+            .unwrap_or_else(|| "Service Unavailable".to_string()),
+    )
+}
+
+#[catch(413)]
+fn payload_too_large(request: &Request) -> ApiError {
+    ApiError::PayloadTooLarge(
+        rejection(request, Status::PayloadTooLarge)
+            .unwrap_or_else(|| "Request body exceeds the limit for this route".to_string()),
+    )
+}
+
+#[catch(422)]
+fn unprocessable_entity() -> ApiError {
+    ApiError::UnprocessableEntity("The request body could not be parsed".to_string())
+}
+
+#[catch(500)]
+fn internal_server_error() -> ApiError {
+    ApiError::Status(Status::InternalServerError)
+}
+
+#[catch(default)]
+fn default(status: Status, _request: &Request) -> ApiError {
+    ApiError::Status(status)
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![
+        bad_request,
+        unauthorized,
+        forbidden,
+        not_found,
+        payload_too_large,
+        service_unavailable,
+        unprocessable_entity,
+        internal_server_error,
+        default
+    ]
+}
@@ -0,0 +1,180 @@
+use std::future::Future;
+use std::time::{Duration, Instant, SystemTime};
+
+use rocket::http::Status;
+use rocket::request::{self, FromRequest};
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use rocket::{get, routes, Request, Route};
+#[cfg(feature = "templates")]
+use rocket_dyn_templates::Metadata;
+use tokio::task;
+use tokio::time::timeout;
+use ulid::Ulid;
+
+#[cfg(feature = "day21")]
+use crate::day21::Day21;
+#[cfg(feature = "database")]
+use crate::infrastructure::with_pool;
+use crate::infrastructure::Infrastructure;
+
+/// Prefix of the keys the readiness check writes to persist, out of the way of keys saved through
+/// day 12. Each probe gets a key of its own, so concurrent probes do not remove each other's.
+pub const PERSIST_PREFIX: &str = "__health";
+
+/// How long a single check may take before its component counts as down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+enum Health {
+    Up,
+    Down,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Component {
+    name: &'static str,
+    status: Health,
+    latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Component {
+    async fn check<F>(name: &'static str, check: F) -> Self
+    where
+        F: Future<Output = Result<(), String>>,
+    {
+        let start = Instant::now();
+        let result = timeout(CHECK_TIMEOUT, check)
+            .await
+            .unwrap_or_else(|_| Err(format!("Timed out after {:?}", CHECK_TIMEOUT)));
+        Component {
+            name,
+            status: match result {
+                Ok(()) => Health::Up,
+                Err(_) => Health::Down,
+            },
+            latency_ms: start.elapsed().as_secs_f64() * 1000.0,
+            error: result.err(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Report {
+    status: Health,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    components: Vec<Component>,
+}
+
+/// Every dependency of the mounted days, checked while the request is guarded.
+struct Readiness(Vec<Component>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Readiness {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let rocket = request.rocket();
+        let infrastructure = rocket.state::<Infrastructure>();
+        let mut components = Vec::new();
+        #[cfg(feature = "database")]
+        components.push(
+            Component::check("database", async {
+                let database = &infrastructure.ok_or("Not managed")?.database;
+                with_pool!(database, |pool| sqlx::query("SELECT 1")
+                    .execute(pool)
+                    .await
+                    .map(|_| ()))
+                .map_err(|e| e.to_string())
+            })
+            .await,
+        );
+        components.push(
+            Component::check("persist", async {
+                let persist = infrastructure.ok_or("Not managed")?.persist.clone();
+                let key = format!("{}-{}", PERSIST_PREFIX, Ulid::new());
+                // Off the async workers, so a slow disk runs into the timeout instead of blocking
+                task::spawn_blocking(move || {
+                    persist
+                        .save(&key, SystemTime::now())
+                        .and_then(|_| persist.load::<SystemTime>(&key))
+                        .and_then(|_| persist.remove(&key))
+                        .map_err(|e| e.to_string())
+                })
+                .await
+                .map_err(|e| e.to_string())?
+            })
+            .await,
+        );
+        #[cfg(feature = "day21")]
+        components.push(
+            Component::check("day21_boundaries", async {
+                match rocket.state::<Day21>() {
+                    Some(day21) if day21.boundaries_loaded() => Ok(()),
+                    Some(_) => Err("Country boundaries are empty".to_string()),
+                    None => Err("Not managed".to_string()),
+                }
+            })
+            .await,
+        );
+        #[cfg(feature = "templates")]
+        components.push(
+            Component::check("templates", async {
+                let metadata = Metadata::from_request(request)
+                    .await
+                    .succeeded()
+                    .ok_or("Templates are not initialized")?;
+                let mut templates = Vec::new();
+                #[cfg(feature = "day14")]
+                templates.extend(["day14_safe", "day14_unsafe"]);
+                #[cfg(feature = "admin")]
+                templates.push("admin");
+                templates
+                    .into_iter()
+                    .find(|template| !metadata.contains_template(template))
+                    .map_or(Ok(()), |template| {
+                        Err(format!("Template {} did not compile", template))
+                    })
+            })
+            .await,
+        );
+        request::Outcome::Success(Readiness(components))
+    }
+}
+
+/// Whether the server is running at all.
+#[get("/health/live")]
+fn live() -> Json<Report> {
+    Json(Report {
+        status: Health::Up,
+        components: Vec::new(),
+    })
+}
+
+/// Whether the server can answer, with the state of each dependency; 503 if any is down.
+#[get("/health/ready")]
+fn ready(readiness: Readiness) -> (Status, Json<Report>) {
+    let components = readiness.0;
+    let status = if components
+        .iter()
+        .all(|component| component.status == Health::Up)
+    {
+        Health::Up
+    } else {
+        Health::Down
+    };
+    let code = match status {
+        Health::Up => Status::Ok,
+        Health::Down => Status::ServiceUnavailable,
+    };
+    (code, Json(Report { status, components }))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![live, ready]
+}
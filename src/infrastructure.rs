@@ -1,7 +1,139 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Debug, Formatter};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "database")]
+use std::str::FromStr;
+
+use rocket::figment::providers::{Format, Toml};
+use rocket::figment::Figment;
+use rocket::serde::Deserialize;
 use shuttle_persist::PersistInstance;
-use sqlx::PgPool;
+use shuttle_secrets::SecretStore;
+#[cfg(feature = "database")]
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+#[cfg(feature = "database")]
+use sqlx::{PgPool, SqlitePool};
 
 pub struct Infrastructure {
-    pub postgres: PgPool,
+    #[cfg(feature = "database")]
+    pub database: Database,
     pub persist: PersistInstance,
+    pub secrets: Secrets,
+}
+
+/// Secret values like API keys, from `Secrets.toml` on Shuttle.
+///
+/// Outside of Shuttle they come from a file like `Secrets.toml` named by `secrets_file` in the
+/// Rocket config, and from its `secrets` table, e.g. `ROCKET_SECRETS={API_KEYS="admin:..."}`,
+/// which takes precedence.
+#[derive(Deserialize, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct Secrets(BTreeMap<String, String>);
+
+impl Secrets {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// Reads secrets from a TOML file in the format of Shuttle's `Secrets.toml`.
+    fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read secrets file {}: {}", path.display(), e))?;
+        Ok(Figment::from(Toml::string(&contents)).extract()?)
+    }
+}
+
+impl From<SecretStore> for Secrets {
+    fn from(store: SecretStore) -> Self {
+        Secrets(store.into_iter().collect())
+    }
+}
+
+/// Lists the names only, so the values do not end up in logs.
+impl Debug for Secrets {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Storage behind the orders and regions endpoints.
+#[cfg(feature = "database")]
+pub enum Database {
+    Postgres(PgPool),
+    /// Embedded alternative that needs no external services.
+    Sqlite(SqlitePool),
+}
+
+/// Evaluates `$body` with `$pool` bound to the pool behind `$database`, whichever backend it is.
+#[cfg(feature = "database")]
+macro_rules! with_pool {
+    ($database:expr, |$pool:ident| $body:expr) => {
+        match $database {
+            $crate::infrastructure::Database::Postgres($pool) => $body,
+            $crate::infrastructure::Database::Sqlite($pool) => $body,
+        }
+    };
+}
+#[cfg(feature = "database")]
+pub(crate) use with_pool;
+
+#[cfg(feature = "database")]
+impl Database {
+    /// Connects to Postgres, or to SQLite if `url` starts with `sqlite:`.
+    pub async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        if !url.starts_with("sqlite:") {
+            return Ok(Database::Postgres(PgPool::connect(url).await?));
+        }
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        // Every connection to an in-memory database would get its own, empty database
+        let pool = if url.contains(":memory:") {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+        } else {
+            SqlitePoolOptions::new()
+        };
+        Ok(Database::Sqlite(pool.connect_with(options).await?))
+    }
+}
+
+/// Where to find the infrastructure when running outside of Shuttle.
+///
+/// Read from `Rocket.toml` or the environment, e.g. `ROCKET_DATABASE_URL`, `ROCKET_PERSIST_DIR` and
+/// `ROCKET_SECRETS` or `ROCKET_SECRETS_FILE`.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Settings {
+    #[cfg(feature = "database")]
+    database_url: String,
+    #[serde(default = "Settings::default_persist_dir")]
+    persist_dir: PathBuf,
+    #[serde(default)]
+    secrets: Secrets,
+    secrets_file: Option<PathBuf>,
+}
+
+impl Settings {
+    fn default_persist_dir() -> PathBuf {
+        PathBuf::from("persist")
+    }
+}
+
+impl Infrastructure {
+    pub async fn from_figment(figment: &Figment) -> Result<Self, Box<dyn Error>> {
+        let settings: Settings = figment.extract()?;
+        let mut secrets = match &settings.secrets_file {
+            Some(path) => Secrets::from_file(path)?,
+            None => Secrets::default(),
+        };
+        secrets.0.extend(settings.secrets.0);
+        Ok(Infrastructure {
+            #[cfg(feature = "database")]
+            database: Database::connect(&settings.database_url).await?,
+            persist: PersistInstance::new(settings.persist_dir)?,
+            secrets,
+        })
+    }
 }
@@ -0,0 +1,150 @@
+use rocket::http::Status;
+use rocket::{get, routes, Build, Config, Rocket};
+#[cfg(feature = "templates")]
+use rocket_dyn_templates::Template;
+
+use crate::auth::ApiKeys;
+use crate::compression::Compression;
+use crate::day::Registry;
+use crate::drain::GracefulShutdown;
+use crate::infrastructure::Infrastructure;
+use crate::metrics::{Metrics, RequestMetrics};
+use crate::rate_limit::RateLimit;
+use crate::security::SecurityHeaders;
+use crate::telemetry::RequestTracing;
+
+#[cfg(feature = "admin")]
+mod admin;
+mod auth;
+pub mod cli;
+mod compression;
+mod day;
+#[cfg(feature = "day1")]
+mod day1;
+#[cfg(feature = "day11")]
+mod day11;
+#[cfg(feature = "day12")]
+mod day12;
+#[cfg(feature = "day13")]
+mod day13;
+#[cfg(feature = "day14")]
+mod day14;
+#[cfg(feature = "day15")]
+mod day15;
+#[cfg(feature = "day18")]
+mod day18;
+#[cfg(feature = "day19")]
+mod day19;
+#[cfg(feature = "day20")]
+mod day20;
+#[cfg(feature = "day21")]
+mod day21;
+#[cfg(feature = "day22")]
+mod day22;
+#[cfg(feature = "day4")]
+mod day4;
+#[cfg(feature = "day5")]
+mod day5;
+#[cfg(feature = "day6")]
+mod day6;
+#[cfg(feature = "day7")]
+mod day7;
+#[cfg(feature = "day8")]
+mod day8;
+#[cfg(feature = "day_negative_1")]
+mod day_negative_1;
+mod drain;
+mod error;
+mod health;
+pub mod infrastructure;
+mod limits;
+mod metrics;
+pub mod openapi;
+mod prefix;
+mod rate_limit;
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(feature = "reindeer")]
+mod reindeer;
+mod security;
+pub mod telemetry;
+#[cfg(feature = "tiebreaker")]
+mod tiebreaker;
+
+/// Registers the days whose cargo feature is enabled, in order.
+macro_rules! registry {
+    ($($feature:literal => $day:ty),* $(,)?) => {{
+        let registry = Registry::default();
+        $(
+            #[cfg(feature = $feature)]
+            let registry = registry.register::<$day>();
+        )*
+        registry
+    }};
+}
+
+#[get("/")]
+fn index() -> Status {
+    Status::Ok
+}
+
+/// The days whose cargo feature is enabled.
+fn registry() -> Registry {
+    registry![
+        "day_negative_1" => day_negative_1::DayNegative1,
+        "day1" => day1::Day1,
+        "day4" => day4::Day4,
+        "day5" => day5::Day5,
+        "day6" => day6::Day6,
+        "day7" => day7::Day7,
+        "day8" => day8::Day8,
+        "day11" => day11::Day11,
+        "day12" => day12::Day12,
+        "day13" => day13::Day13,
+        "day14" => day14::Day14,
+        "day15" => day15::Day15,
+        "day18" => day18::Day18,
+        "day19" => day19::Day19,
+        "day20" => day20::Day20,
+        "day21" => day21::Day21,
+        "day22" => day22::Day22,
+        "tiebreaker" => tiebreaker::Tiebreaker,
+        "reindeer" => reindeer::ReindeerRegistry,
+    ]
+}
+
+/// Builds the server with every day mounted, independent of how `infrastructure` was provisioned.
+pub fn build(infrastructure: Infrastructure) -> Rocket<Build> {
+    let rocket = rocket::custom(limits::configure(Config::figment()))
+        .manage(infrastructure)
+        .manage(Metrics::default());
+    // Shared by the days calling other APIs, so they reuse its connections
+    #[cfg(feature = "upstream")]
+    let rocket = rocket.manage(reqwest::Client::new());
+    let rocket = registry()
+        .build(rocket)
+        .mount("/", routes![index])
+        .mount("/", Metrics::routes())
+        .mount("/", health::routes())
+        .register("/", error::catchers())
+        .attach(ApiKeys::fairing())
+        .attach(RateLimit)
+        .attach(GracefulShutdown)
+        .attach(security::shield())
+        .attach(SecurityHeaders);
+    // Records the responses as they are before compression
+    #[cfg(feature = "record")]
+    let rocket = rocket.attach(record::Recording);
+    let rocket = rocket
+        .attach(Compression)
+        .attach(RequestTracing)
+        .attach(RequestMetrics);
+    // Shared by day 14 and the dashboard, while the fairing must only be attached once
+    #[cfg(feature = "templates")]
+    let rocket = rocket.attach(Template::fairing());
+    #[cfg(feature = "admin")]
+    let rocket = rocket
+        .manage(admin::CsrfToken::default())
+        .mount("/", admin::routes());
+    rocket
+}
@@ -0,0 +1,218 @@
+#[cfg(feature = "day11")]
+use multer::{Constraints, Multipart, SizeLimit};
+#[cfg(feature = "extractors")]
+use rocket::data::{self, Data, FromData};
+use rocket::data::{ByteUnit, Limits, ToByteUnit};
+use rocket::figment::Figment;
+#[cfg(feature = "day20")]
+use rocket::tokio::{fs::File, io::BufWriter, task};
+use rocket::Config;
+#[cfg(feature = "limits")]
+use rocket::{http::Status, Request};
+#[cfg(feature = "day20")]
+use tempfile::TempPath;
+
+#[cfg(feature = "day20")]
+use crate::drain::Drain;
+#[cfg(feature = "limits")]
+use crate::error::ApiError;
+
+/// Limit for plain text bodies, like the star charts of day 22.
+pub const TEXT: &str = "text";
+/// Limit for images uploaded in forms, like the decorations of day 11.
+pub const IMAGE: &str = "image";
+/// Limit for archives streamed to disk, like the tarballs of day 20.
+pub const ARCHIVE: &str = "archive";
+/// Limit for batches of items, like the packet ID lists of day 1.
+pub const BATCH: &str = "batch";
+
+fn defaults() -> [(&'static str, ByteUnit); 4] {
+    [
+        (TEXT, 1.mebibytes()),
+        (IMAGE, 10.mebibytes()),
+        (ARCHIVE, 512.mebibytes()),
+        (BATCH, 16.mebibytes()),
+    ]
+}
+
+/// Adds the default of every named limit that is not configured in `figment`, e.g. through
+/// `ROCKET_LIMITS={archive="1GiB"}`.
+pub fn configure(figment: Figment) -> Figment {
+    let configured = figment
+        .extract_inner::<Limits>(Config::LIMITS)
+        .unwrap_or_default();
+    let limits = defaults()
+        .into_iter()
+        .fold(configured, |limits, (name, limit)| match limits.get(name) {
+            Some(_) => limits,
+            None => limits.limit(name, limit),
+        });
+    figment.merge((Config::LIMITS, limits))
+}
+
+#[cfg(feature = "limits")]
+pub fn limit(request: &Request<'_>, name: &'static str) -> ByteUnit {
+    request.limits().get(name).unwrap_or(Limits::BYTES)
+}
+
+#[cfg(feature = "limits")]
+pub fn exceeded(request: &Request<'_>, name: &'static str, limit: ByteUnit) -> (Status, ApiError) {
+    ApiError::PayloadTooLarge(format!(
+        "Request body exceeds the {} limit of {}",
+        name, limit
+    ))
+    .reject(request)
+}
+
+/// A UTF-8 request body, capped by the [`TEXT`] limit.
+#[cfg(feature = "text")]
+pub struct Text(pub String);
+
+#[cfg(feature = "text")]
+#[rocket::async_trait]
+impl<'r> FromData<'r> for Text {
+    type Error = ApiError;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = limit(request, TEXT);
+        match data.open(limit).into_string().await {
+            Ok(text) if text.is_complete() => data::Outcome::Success(Text(text.into_inner())),
+            Ok(_) => data::Outcome::Error(exceeded(request, TEXT, limit)),
+            Err(e) => data::Outcome::Error(
+                ApiError::BadRequest(format!("Could not read body as text: {}", e)).reject(request),
+            ),
+        }
+    }
+}
+
+/// A request body streamed into a temporary file, capped by the [`ARCHIVE`] limit.
+///
+/// The file is removed once the upload is dropped, or with the scratch directory of the [`Drain`]
+/// it is created in on shutdown.
+#[cfg(feature = "day20")]
+pub struct Upload(TempPath);
+
+#[cfg(feature = "day20")]
+impl Upload {
+    pub fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(feature = "day20")]
+#[rocket::async_trait]
+impl<'r> FromData<'r> for Upload {
+    type Error = ApiError;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = limit(request, ARCHIVE);
+        let dir = match request.rocket().state::<Drain>() {
+            Some(drain) => drain.scratch().to_path_buf(),
+            None => request.rocket().config().temp_dir.relative(),
+        };
+        let file = match task::spawn_blocking(move || tempfile::NamedTempFile::new_in(dir))
+            .await
+            .map_err(std::io::Error::from)
+            .and_then(|file| file)
+        {
+            Ok(file) => file,
+            Err(e) => {
+                return data::Outcome::Error(
+                    ApiError::internal("Could not create temp file", e).reject(request),
+                )
+            }
+        };
+        let (file, path) = file.into_parts();
+        match data
+            .open(limit)
+            .stream_to(BufWriter::new(File::from_std(file)))
+            .await
+        {
+            Ok(written) if written.complete => data::Outcome::Success(Upload(path)),
+            Ok(_) => data::Outcome::Error(exceeded(request, ARCHIVE, limit)),
+            Err(e) => data::Outcome::Error(
+                ApiError::internal("Could not write temp file", e).reject(request),
+            ),
+        }
+    }
+}
+
+/// The `image` file of a `multipart/form-data` body, capped by the [`IMAGE`] limit.
+///
+/// Parses the form itself rather than through rocket's `Form`, whose `data-form` limit applies to
+/// all forms alike.
+#[cfg(feature = "day11")]
+pub struct Image(pub Vec<u8>);
+
+#[cfg(feature = "day11")]
+impl Image {
+    const FIELD: &'static str = "image";
+
+    fn rejected(request: &Request<'_>, limit: ByteUnit, e: multer::Error) -> (Status, ApiError) {
+        if Self::too_large(&e) {
+            return exceeded(request, IMAGE, limit);
+        }
+        ApiError::BadRequest(format!("Could not read form: {}", e)).reject(request)
+    }
+
+    /// Size errors of the whole stream surface wrapped in a read error of the field.
+    fn too_large(e: &multer::Error) -> bool {
+        match e {
+            multer::Error::FieldSizeExceeded { .. } | multer::Error::StreamSizeExceeded { .. } => {
+                true
+            }
+            multer::Error::StreamReadFailed(e) => e
+                .downcast_ref::<multer::Error>()
+                .is_some_and(Self::too_large),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "day11")]
+#[rocket::async_trait]
+impl<'r> FromData<'r> for Image {
+    type Error = ApiError;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = limit(request, IMAGE);
+        let Some(boundary) = request
+            .content_type()
+            .filter(|content_type| content_type.is_form_data())
+            .and_then(|content_type| content_type.param("boundary"))
+        else {
+            return data::Outcome::Error(
+                ApiError::Status(Status::UnsupportedMediaType).reject(request),
+            );
+        };
+        // Leaves room for the other fields and the multipart framing around the image
+        let constraints = Constraints::new().size_limit(
+            SizeLimit::new()
+                .whole_stream((limit + 64.kibibytes()).as_u64())
+                .for_field(Self::FIELD, limit.as_u64()),
+        );
+        let mut multipart = Multipart::with_reader_with_constraints(
+            data.open(ByteUnit::max_value()),
+            boundary,
+            constraints,
+        );
+        loop {
+            match multipart.next_field().await {
+                Ok(Some(field)) if field.name() == Some(Self::FIELD) => {
+                    return match field.bytes().await {
+                        Ok(bytes) => data::Outcome::Success(Image(bytes.to_vec())),
+                        Err(e) => data::Outcome::Error(Self::rejected(request, limit, e)),
+                    };
+                }
+                Ok(Some(_)) => continue,
+                Ok(None) => {
+                    return data::Outcome::Error(
+                        ApiError::UnprocessableEntity(format!("Form has no {} field", Self::FIELD))
+                            .reject(request),
+                    )
+                }
+                Err(e) => return data::Outcome::Error(Self::rejected(request, limit, e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+#[cfg(feature = "day8")]
+use std::time::Duration;
+
+use prometheus::core::Collector;
+#[cfg(feature = "day8")]
+use prometheus::Histogram;
+#[cfg(feature = "day19")]
+use prometheus::IntGauge;
+#[cfg(any(feature = "day19", feature = "database"))]
+use prometheus::IntGaugeVec;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::request::{self, FromRequest};
+use rocket::{get, routes, Data, Request, Response, Route};
+
+#[cfg(feature = "day19")]
+use crate::day19::Day19;
+use crate::error::ApiError;
+#[cfg(feature = "database")]
+use crate::infrastructure::{with_pool, Infrastructure};
+use crate::telemetry;
+
+/// Prometheus metrics of the server, exposed at `/metrics`.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    #[cfg(feature = "day8")]
+    pokeapi_requests: IntCounterVec,
+    #[cfg(feature = "day8")]
+    pokeapi_duration: Histogram,
+    #[cfg(feature = "day19")]
+    twit_views: IntGauge,
+    #[cfg(feature = "day19")]
+    websocket_clients: IntGaugeVec,
+    #[cfg(feature = "database")]
+    database_connections: IntGaugeVec,
+}
+
+const INVALID: &str = "Metric definitions are invalid";
+
+fn register<C: Collector + Clone + 'static>(
+    registry: &Registry,
+    collector: prometheus::Result<C>,
+) -> C {
+    let collector = collector.expect(INVALID);
+    registry
+        .register(Box::new(collector.clone()))
+        .expect(INVALID);
+    collector
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let registry = Registry::new_custom(Some("cch23".to_string()), None).expect(INVALID);
+        Metrics {
+            requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("http_requests_total", "Answered requests"),
+                    &["method", "route", "status"],
+                ),
+            ),
+            request_duration: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "http_request_duration_seconds",
+                        "Time taken to answer requests",
+                    ),
+                    &["method", "route"],
+                ),
+            ),
+            #[cfg(feature = "day8")]
+            pokeapi_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("pokeapi_requests_total", "Requests sent to the PokéAPI"),
+                    &["outcome"],
+                ),
+            ),
+            #[cfg(feature = "day8")]
+            pokeapi_duration: register(
+                &registry,
+                Histogram::with_opts(HistogramOpts::new(
+                    "pokeapi_request_duration_seconds",
+                    "Time taken by the PokéAPI to answer",
+                )),
+            ),
+            #[cfg(feature = "day19")]
+            twit_views: register(
+                &registry,
+                IntGauge::new("twit_views", "Tweets delivered to bird app clients"),
+            ),
+            #[cfg(feature = "day19")]
+            websocket_clients: register(
+                &registry,
+                IntGaugeVec::new(
+                    Opts::new("websocket_clients", "Bird app clients connected per room"),
+                    &["room"],
+                ),
+            ),
+            #[cfg(feature = "database")]
+            database_connections: register(
+                &registry,
+                IntGaugeVec::new(
+                    Opts::new("database_connections", "Connections in the database pool"),
+                    &["state"],
+                ),
+            ),
+            registry,
+        }
+    }
+}
+
+impl Metrics {
+    pub fn routes() -> Vec<Route> {
+        routes![metrics]
+    }
+
+    /// Records a call to the PokéAPI, `outcome` being the status code or `error` if there was none.
+    #[cfg(feature = "day8")]
+    pub fn observe_pokeapi(&self, outcome: &str, duration: Duration) {
+        self.pokeapi_requests.with_label_values(&[outcome]).inc();
+        self.pokeapi_duration.observe(duration.as_secs_f64());
+    }
+
+    #[cfg(feature = "day19")]
+    fn collect_day19(&self, day19: &Day19) {
+        self.twit_views.set(day19.views() as i64);
+        self.websocket_clients.reset();
+        for (room, clients) in day19.clients() {
+            self.websocket_clients
+                .with_label_values(&[&room.to_string()])
+                .set(clients as i64);
+        }
+    }
+
+    #[cfg(feature = "database")]
+    fn collect_database(&self, infrastructure: &Infrastructure) {
+        let (size, idle) = with_pool!(&infrastructure.database, |pool| (
+            pool.size(),
+            pool.num_idle()
+        ));
+        self.database_connections
+            .with_label_values(&["idle"])
+            .set(idle as i64);
+        self.database_connections
+            .with_label_values(&["in_use"])
+            .set(size as i64 - idle as i64);
+    }
+}
+
+/// The metrics, with the gauges mirroring the state of the mounted days brought up to date.
+struct Collected<'r>(&'r Metrics);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Collected<'r> {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let rocket = request.rocket();
+        let Some(metrics) = rocket.state::<Metrics>() else {
+            return request::Outcome::Forward(rocket::http::Status::NotFound);
+        };
+        #[cfg(feature = "day19")]
+        if let Some(day19) = rocket.state::<Day19>() {
+            metrics.collect_day19(day19);
+        }
+        #[cfg(feature = "database")]
+        if let Some(infrastructure) = rocket.state::<Infrastructure>() {
+            metrics.collect_database(infrastructure);
+        }
+        request::Outcome::Success(Collected(metrics))
+    }
+}
+
+#[get("/metrics")]
+fn metrics(metrics: Collected<'_>) -> Result<(ContentType, Vec<u8>), ApiError> {
+    let metrics = metrics.0;
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder
+        .encode(&metrics.registry.gather(), &mut buffer)
+        .map_err(|e| ApiError::internal("Could not encode metrics", e))?;
+    let content_type =
+        ContentType::parse_flexible(encoder.format_type()).unwrap_or(ContentType::Text);
+    Ok((content_type, buffer))
+}
+
+/// Counts answered requests and their latency per mounted route.
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        telemetry::elapsed(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(metrics) = request.rocket().state::<Metrics>() else {
+            return;
+        };
+        let method = request.method().as_str();
+        let route = request
+            .route()
+            .map(|route| route.uri.as_str())
+            .unwrap_or("unmatched");
+        metrics
+            .requests
+            .with_label_values(&[method, route, &response.status().code.to_string()])
+            .inc();
+        metrics
+            .request_duration
+            .with_label_values(&[method, route])
+            .observe(telemetry::elapsed(request).as_secs_f64());
+    }
+}
@@ -0,0 +1,311 @@
+use rocket::response::content::{RawCss, RawHtml, RawJavaScript};
+use rocket::serde::json::serde_json::{json, Map, Value};
+use rocket::serde::json::Json;
+use rocket::{get, routes, Route, State};
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+
+use crate::auth::Scope;
+use crate::error::Problem;
+use crate::security::WithPolicy;
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+fn schema_for<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+    generator.subschema_for::<T>()
+}
+
+/// Payload of a request or response.
+pub enum Body {
+    Empty,
+    Json(SchemaFn),
+    Text,
+    Html,
+    /// Raw bytes of the given content type.
+    Binary(&'static str),
+    /// A `multipart/form-data` form with a single file in the given field.
+    Upload(&'static str),
+}
+
+impl Body {
+    pub fn json<T: JsonSchema>() -> Self {
+        Body::Json(schema_for::<T>)
+    }
+
+    fn content(&self, generator: &mut SchemaGenerator) -> Option<Value> {
+        let (content_type, schema) = match self {
+            Body::Empty => return None,
+            Body::Json(schema) => ("application/json", json!(schema(generator))),
+            Body::Text => ("text/plain", json!({ "type": "string" })),
+            Body::Html => ("text/html", json!({ "type": "string" })),
+            Body::Binary(content_type) => (
+                *content_type,
+                json!({ "type": "string", "format": "binary" }),
+            ),
+            Body::Upload(field) => (
+                "multipart/form-data",
+                json!({
+                    "type": "object",
+                    "required": [field],
+                    "properties": { *field: { "type": "string", "format": "binary" } },
+                }),
+            ),
+        };
+        Some(json!({ content_type: { "schema": schema } }))
+    }
+}
+
+/// Documentation of a route beyond what rocket knows about it, matched by the route's name.
+pub struct Operation {
+    route: &'static str,
+    summary: &'static str,
+    request: Body,
+    response: Body,
+    websocket: Option<SchemaFn>,
+    parameters: Vec<(&'static str, SchemaFn)>,
+    scope: Option<Scope>,
+}
+
+impl Operation {
+    pub fn new(route: &'static str, summary: &'static str) -> Self {
+        Operation {
+            route,
+            summary,
+            request: Body::Empty,
+            response: Body::Empty,
+            websocket: None,
+            parameters: Vec::new(),
+            scope: None,
+        }
+    }
+
+    /// A websocket endpoint the client sends text messages like `T` to, while the messages the
+    /// server sends are documented as the response.
+    pub fn websocket<T: JsonSchema>(route: &'static str, summary: &'static str) -> Self {
+        Operation {
+            websocket: Some(schema_for::<T>),
+            ..Operation::new(route, summary)
+        }
+    }
+
+    pub fn request(mut self, body: Body) -> Self {
+        self.request = body;
+        self
+    }
+
+    pub fn response(mut self, body: Body) -> Self {
+        self.response = body;
+        self
+    }
+
+    /// Types the path or query parameter `name`, which is otherwise documented as a string.
+    pub fn parameter<T: JsonSchema>(mut self, name: &'static str) -> Self {
+        self.parameters.push((name, schema_for::<T>));
+        self
+    }
+
+    /// Marks the route as requiring an API key with at least `scope`.
+    pub fn requires(mut self, scope: Scope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    pub fn route(&self) -> &'static str {
+        self.route
+    }
+
+    pub fn documents(&self, route: &Route) -> bool {
+        route.name.as_deref() == Some(self.route)
+    }
+}
+
+/// A mounted route together with its documentation, if any.
+pub struct Documented<'a> {
+    pub tag: &'static str,
+    pub route: &'a Route,
+    pub operation: Option<&'a Operation>,
+}
+
+pub struct OpenApi(Value);
+
+impl OpenApi {
+    pub fn new(description: &str, routes: &[Documented]) -> Self {
+        let mut generator = SchemaGenerator::new(SchemaSettings::openapi3());
+        let problem = generator.subschema_for::<Problem>();
+        let mut paths = Map::new();
+        for documented in routes {
+            let (path, parameters) = Self::path(documented, &mut generator);
+            let item = paths
+                .entry(path)
+                .or_insert_with(|| json!({}))
+                .as_object_mut()
+                .expect("Path items are objects");
+            let operation = Self::operation(documented, parameters, &problem, &mut generator);
+            item.insert(documented.route.method.as_str().to_lowercase(), operation);
+        }
+        let schemas = generator
+            .take_definitions()
+            .into_iter()
+            .map(|(name, schema)| (name, json!(schema)))
+            .collect::<Map<_, _>>();
+        OpenApi(json!({
+            "openapi": "3.0.3",
+            "info": {
+                "title": "shuttle-cch23",
+                "description": description,
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "paths": paths,
+            "components": {
+                "schemas": schemas,
+                "securitySchemes": {
+                    "bearer": { "type": "http", "scheme": "bearer" },
+                    "apiKey": { "type": "apiKey", "in": "header", "name": "X-Api-Key" },
+                },
+            },
+        }))
+    }
+
+    pub fn routes() -> Vec<Route> {
+        routes![
+            openapi,
+            docs,
+            docs_script,
+            swagger_ui_script,
+            swagger_ui_style
+        ]
+    }
+
+    /// Converts rocket's `/1/<num..>?<limit>` into `/1/{num}` plus the parameters in path and query.
+    fn path(documented: &Documented, generator: &mut SchemaGenerator) -> (String, Vec<Value>) {
+        let mut parameters = Vec::new();
+        let mut parameter = |name: &str, location: &str| {
+            let schema = documented
+                .operation
+                .and_then(|operation| {
+                    operation
+                        .parameters
+                        .iter()
+                        .find(|(parameter, _)| *parameter == name)
+                })
+                .map(|(_, schema)| json!(schema(generator)))
+                .unwrap_or_else(|| json!({ "type": "string" }));
+            parameters.push(json!({
+                "name": name,
+                "in": location,
+                "required": location == "path",
+                "schema": schema,
+            }));
+        };
+        let segments = documented
+            .route
+            .uri
+            .path()
+            .split('/')
+            .map(|segment| match Self::dynamic(segment) {
+                Some(name) => {
+                    parameter(name, "path");
+                    format!("{{{}}}", name)
+                }
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>();
+        for name in documented
+            .route
+            .uri
+            .query()
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .filter_map(Self::dynamic)
+        {
+            parameter(name, "query");
+        }
+        (segments.join("/"), parameters)
+    }
+
+    fn dynamic(segment: &str) -> Option<&str> {
+        segment
+            .strip_prefix('<')
+            .and_then(|segment| segment.strip_suffix('>'))
+            .map(|name| name.trim_end_matches(".."))
+    }
+
+    fn operation(
+        documented: &Documented,
+        parameters: Vec<Value>,
+        problem: &Schema,
+        generator: &mut SchemaGenerator,
+    ) -> Value {
+        let route = documented.route;
+        let mut operation = json!({
+            "tags": [documented.tag],
+            "operationId": format!(
+                "{}_{}",
+                documented.tag,
+                route.name.as_deref().unwrap_or_default()
+            ),
+            "parameters": parameters,
+        });
+        let mut responses = json!({
+            "default": {
+                "description": "Problem",
+                "content": { "application/problem+json": { "schema": problem } },
+            },
+        });
+        let mut success = json!({ "description": "Success" });
+        let mut status = "200";
+        if let Some(documentation) = documented.operation {
+            operation["summary"] = json!(documentation.summary);
+            if let Some(content) = documentation.request.content(generator) {
+                operation["requestBody"] = json!({ "required": true, "content": content });
+            }
+            if let Some(content) = documentation.response.content(generator) {
+                success["content"] = content;
+            }
+            if let Some(message) = documentation.websocket {
+                status = "101";
+                success["description"] =
+                    json!("Switching protocols, then messages sent by the server");
+                operation["x-websocket"] = json!({ "messages": message(generator) });
+            }
+            if let Some(scope) = documentation.scope {
+                operation["description"] =
+                    json!(format!("Requires an API key with the {} scope.", scope));
+                operation["security"] = json!([{ "bearer": [] }, { "apiKey": [] }]);
+            }
+        }
+        responses[status] = success;
+        operation["responses"] = responses;
+        operation
+    }
+}
+
+#[get("/openapi.json")]
+fn openapi(openapi: &State<OpenApi>) -> Json<Value> {
+    Json(openapi.0.clone())
+}
+
+/// Swagger UI is served by the app itself, at the version vendored in `assets/swagger-ui`.
+const DOCS_POLICY: &str = "default-src 'none'; script-src 'self'; style-src 'self'; \
+    img-src 'self' data:; connect-src 'self'; frame-ancestors 'none'";
+
+#[get("/docs")]
+fn docs() -> WithPolicy<RawHtml<&'static str>> {
+    WithPolicy(DOCS_POLICY, RawHtml(include_str!("../assets/docs.html")))
+}
+
+#[get("/docs/docs.js")]
+fn docs_script() -> RawJavaScript<&'static str> {
+    RawJavaScript(include_str!("../assets/docs.js"))
+}
+
+#[get("/docs/swagger-ui-bundle.js")]
+fn swagger_ui_script() -> RawJavaScript<&'static str> {
+    RawJavaScript(include_str!("../assets/swagger-ui/swagger-ui-bundle.js"))
+}
+
+#[get("/docs/swagger-ui.css")]
+fn swagger_ui_style() -> RawCss<&'static str> {
+    RawCss(include_str!("../assets/swagger-ui/swagger-ui.css"))
+}
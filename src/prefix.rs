@@ -0,0 +1,14 @@
+/// The entry whose prefix is the longest one covering `path`, matching whole segments only, so
+/// `/1` covers `/1` and `/1/2` but not `/12`.
+pub fn longest<'a, T>(
+    path: &str,
+    entries: impl IntoIterator<Item = (&'a str, T)>,
+) -> Option<(&'a str, T)> {
+    entries
+        .into_iter()
+        .filter(|(prefix, _)| {
+            path.strip_prefix(prefix.trim_end_matches('/'))
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+        .max_by_key(|(prefix, _)| prefix.len())
+}
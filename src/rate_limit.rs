@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use rocket::route::{self, Handler};
+use rocket::serde::Deserialize;
+use rocket::{Build, Data, Request, Response, Rocket, Route};
+use tracing::{debug, error};
+
+use crate::auth::{self, ApiKeys, Hash};
+use crate::error::ApiError;
+use crate::prefix;
+
+/// How many requests a client may send to a prefix.
+///
+/// A client can burst up to `burst` requests, after which it gets another one every
+/// `60 / per_minute` seconds.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(crate = "rocket::serde")]
+struct Quota {
+    burst: u32,
+    per_minute: u32,
+}
+
+impl Quota {
+    const fn new(burst: u32, per_minute: u32) -> Self {
+        Quota { burst, per_minute }
+    }
+
+    /// Whether a client can ever send a request: a bucket that holds no tokens or never refills
+    /// would answer 429 forever.
+    fn allows_requests(&self) -> bool {
+        self.burst > 0 && self.per_minute > 0
+    }
+
+    fn per_second(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+/// Quotas of the routes that are expensive to answer, next to the one of everything else.
+fn defaults() -> [(&'static str, Quota); 4] {
+    [
+        ("/", Quota::new(120, 600)),
+        // Fans out to PokéAPI
+        ("/8", Quota::new(10, 60)),
+        // Decodes images
+        ("/11", Quota::new(10, 60)),
+        // Unpacks archives
+        ("/20", Quota::new(5, 30)),
+    ]
+}
+
+/// Who a bucket belongs to: the API key if the request has a known one, its IP otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    ApiKey(Hash),
+    Ip(Option<IpAddr>),
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(quota: &Quota, now: Instant) -> Self {
+        Bucket {
+            tokens: f64::from(quota.burst),
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, quota: &Quota, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * quota.per_second()).min(f64::from(quota.burst));
+        self.updated = now;
+    }
+
+    /// Takes a token, answering how many are left or how long until the next one.
+    fn take(&mut self, quota: &Quota, now: Instant) -> Result<u32, Duration> {
+        self.refill(quota, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(self.tokens as u32)
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / quota.per_second(),
+            ))
+        }
+    }
+}
+
+/// What the fairing decided about the request, for the routes and headers to act on.
+#[derive(Debug, Clone)]
+enum Decision {
+    Unlimited,
+    Allowed {
+        limit: u32,
+        remaining: u32,
+    },
+    Limited {
+        prefix: String,
+        quota: Quota,
+        retry_after: Duration,
+    },
+}
+
+/// Token buckets per quota prefix and client.
+struct Limiter {
+    quotas: Vec<(String, Quota)>,
+    buckets: Mutex<HashMap<(usize, Client), Bucket>>,
+}
+
+impl Limiter {
+    /// Above this many buckets, the ones that refilled completely get dropped.
+    const PRUNE_ABOVE: usize = 10_000;
+
+    fn new(quotas: BTreeMap<String, Quota>) -> Self {
+        Limiter {
+            quotas: quotas.into_iter().collect(),
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// The quota with the longest prefix covering `path`.
+    fn quota(&self, path: &str) -> Option<(usize, &str, &Quota)> {
+        let entries = self.quotas.iter().enumerate();
+        prefix::longest(
+            path,
+            entries.map(|(index, (prefix, quota))| (prefix.as_str(), (index, quota))),
+        )
+        .map(|(prefix, (index, quota))| (index, prefix, quota))
+    }
+
+    fn decide(&self, path: &str, client: Client) -> Decision {
+        let Some((index, prefix, quota)) = self.quota(path) else {
+            return Decision::Unlimited;
+        };
+        let now = Instant::now();
+        let mut buckets = self
+            .buckets
+            .lock()
+            .expect("Rate limit buckets are poisoned");
+        if buckets.len() > Self::PRUNE_ABOVE {
+            let quotas = &self.quotas;
+            buckets.retain(|(index, _), bucket| {
+                let quota = &quotas[*index].1;
+                bucket.refill(quota, now);
+                bucket.tokens < f64::from(quota.burst)
+            });
+        }
+        let taken = buckets
+            .entry((index, client))
+            .or_insert_with(|| Bucket::full(quota, now))
+            .take(quota, now);
+        match taken {
+            Ok(remaining) => Decision::Allowed {
+                limit: quota.burst,
+                remaining,
+            },
+            Err(retry_after) => Decision::Limited {
+                prefix: prefix.to_string(),
+                quota: *quota,
+                retry_after,
+            },
+        }
+    }
+}
+
+/// Answers the requests the fairing limited, ahead of every other route.
+#[derive(Clone)]
+struct Rejection;
+
+impl Rejection {
+    /// Ranks of the other routes are at least -12.
+    const RANK: isize = -20;
+
+    fn routes() -> Vec<Route> {
+        [
+            Method::Get,
+            Method::Put,
+            Method::Post,
+            Method::Delete,
+            Method::Options,
+            Method::Head,
+            Method::Patch,
+        ]
+        .into_iter()
+        .map(|method| {
+            let mut route = Route::ranked(Self::RANK, method, "/<_..>", Rejection);
+            route.name = Some("rate_limited".into());
+            route
+        })
+        .collect()
+    }
+}
+
+#[rocket::async_trait]
+impl Handler for Rejection {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        match request.local_cache(|| Decision::Unlimited) {
+            Decision::Limited {
+                prefix,
+                quota,
+                retry_after,
+            } => route::Outcome::from(
+                request,
+                ApiError::TooManyRequests(format!(
+                    "Rate limit of {} requests per minute for {} exceeded, retry in {}s",
+                    quota.per_minute,
+                    prefix,
+                    retry_after_secs(*retry_after)
+                )),
+            ),
+            _ => route::Outcome::forward(data, Status::NotFound),
+        }
+    }
+}
+
+fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_secs_f64().ceil() as u64
+}
+
+/// Limits the requests of every client with a token bucket per quota prefix.
+///
+/// Quotas are read from the `rate_limits` table of the Rocket config, keyed by prefix, e.g.
+/// `ROCKET_RATE_LIMITS={"/20"={burst=2,per_minute=10}}`; the longest matching prefix applies.
+/// Answers `429 Too Many Requests` with `Retry-After` once a bucket is empty, and reports the
+/// quota left in `X-RateLimit-Limit` and `X-RateLimit-Remaining` otherwise.
+pub struct RateLimit;
+
+impl RateLimit {
+    const CONFIG: &'static str = "rate_limits";
+
+    fn client(request: &Request<'_>) -> Client {
+        auth::presented_key(request)
+            .zip(request.rocket().state::<ApiKeys>())
+            .and_then(|(key, keys)| keys.known(key))
+            .map(Client::ApiKey)
+            .unwrap_or_else(|| Client::Ip(request.client_ip()))
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RateLimit {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limit",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let configured = match rocket
+            .figment()
+            .extract_inner::<BTreeMap<String, Quota>>(Self::CONFIG)
+        {
+            Ok(configured) => configured,
+            Err(e) if e.missing() => BTreeMap::new(),
+            Err(e) => {
+                error!("Invalid {} config: {}", Self::CONFIG, e);
+                return Err(rocket);
+            }
+        };
+        let mut quotas = defaults()
+            .into_iter()
+            .map(|(prefix, quota)| (prefix.to_string(), quota))
+            .collect::<BTreeMap<_, _>>();
+        quotas.extend(configured);
+        if let Some((prefix, _)) = quotas
+            .iter()
+            .find(|(prefix, quota)| !prefix.starts_with('/') || !quota.allows_requests())
+        {
+            error!(
+                "Invalid {} config for {}: prefixes start with / and quotas allow a burst of at \
+                least one request, refilled at least once a minute",
+                Self::CONFIG,
+                prefix
+            );
+            return Err(rocket);
+        }
+        debug!(?quotas, "Configured rate limits");
+        Ok(rocket
+            .manage(Limiter::new(quotas))
+            .mount("/", Rejection::routes()))
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(limiter) = request.rocket().state::<Limiter>() else {
+            return;
+        };
+        let decision = limiter.decide(request.uri().path().as_str(), Self::client(request));
+        request.local_cache(|| decision);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        match request.local_cache(|| Decision::Unlimited) {
+            Decision::Unlimited => {}
+            Decision::Allowed { limit, remaining } => {
+                response.set_header(Header::new("X-RateLimit-Limit", limit.to_string()));
+                response.set_header(Header::new("X-RateLimit-Remaining", remaining.to_string()));
+            }
+            Decision::Limited {
+                quota, retry_after, ..
+            } => {
+                response.set_header(Header::new("X-RateLimit-Limit", quota.burst.to_string()));
+                response.set_header(Header::new("X-RateLimit-Remaining", "0"));
+                response.set_header(Header::new(
+                    "Retry-After",
+                    retry_after_secs(*retry_after).to_string(),
+                ));
+            }
+        }
+    }
+}
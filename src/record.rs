@@ -0,0 +1,451 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose, Engine};
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::figment::value::magic::RelativePathBuf;
+use rocket::http::{HeaderMap, Status};
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::fs::OpenOptions;
+use rocket::tokio::io::AsyncWriteExt;
+use rocket::tokio::sync::Mutex;
+use rocket::{Build, Data, Request, Response, Rocket};
+use tracing::{error, warn};
+
+use crate::prefix;
+use crate::telemetry::RequestId;
+
+/// Headers never written to a recording, since they carry credentials.
+const SECRET_HEADERS: [&str; 3] = ["Authorization", "X-Api-Key", "Cookie"];
+
+/// Marks replayed requests with the ID of the recorded one, so they are not recorded again.
+const REPLAY_HEADER: &str = "X-Replay-Of";
+
+/// Request headers replay leaves to the HTTP client instead of sending the recorded ones.
+const CLIENT_HEADERS: [&str; 5] = [
+    "Host",
+    "Content-Length",
+    "Transfer-Encoding",
+    "Connection",
+    "Accept-Encoding",
+];
+
+/// A body as it went over the wire, as text if it is UTF-8.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum Body {
+    Text(String),
+    Base64(String),
+}
+
+impl Body {
+    fn new(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Body::Text(text),
+            Err(e) => Body::Base64(general_purpose::STANDARD.encode(e.into_bytes())),
+        }
+    }
+
+    fn bytes(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        match self {
+            Body::Text(text) => Ok(text.clone().into_bytes()),
+            Body::Base64(encoded) => general_purpose::STANDARD.decode(encoded),
+        }
+    }
+}
+
+/// One request and the response it got, a line of a recording.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Exchange {
+    request_id: String,
+    /// Milliseconds since the Unix epoch.
+    recorded_at: u128,
+    method: String,
+    uri: String,
+    headers: Vec<(String, String)>,
+    /// Missing in recordings made before bodies were recorded in part.
+    body: Option<Body>,
+    /// Whether `body` is only the start of the request body, which then cannot be replayed.
+    #[serde(default)]
+    truncated: bool,
+    status: u16,
+    response_headers: Vec<(String, String)>,
+    /// Missing if the response was streamed or larger than `max_body`.
+    response_body: Option<Body>,
+}
+
+fn headers(headers: &HeaderMap<'_>) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|header| {
+            !SECRET_HEADERS
+                .iter()
+                .any(|secret| header.name().as_str().eq_ignore_ascii_case(secret))
+        })
+        .map(|header| (header.name().to_string(), header.value().to_string()))
+        .collect()
+}
+
+/// What gets recorded, read from the `record` table of the Rocket config, e.g.
+/// `ROCKET_RECORD={prefixes=["/4","/22"]}`.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Settings {
+    /// Requests to these prefixes are recorded, matching whole segments only.
+    prefixes: Vec<String>,
+    /// Where the recordings go, relative to the config file if there is one.
+    #[serde(default = "Settings::default_dir")]
+    dir: RelativePathBuf,
+    /// Request bodies are recorded up to this many bytes, though Rocket lets fairings peek at no
+    /// more than 512; response bodies larger than this are left out of the recording.
+    #[serde(default = "Settings::default_max_body")]
+    max_body: usize,
+}
+
+impl Settings {
+    fn default_dir() -> RelativePathBuf {
+        PathBuf::from("recordings").into()
+    }
+
+    fn default_max_body() -> usize {
+        1024 * 1024
+    }
+
+    fn prefix(&self, path: &str) -> Option<&str> {
+        let entries = self.prefixes.iter().map(|prefix| (prefix.as_str(), ()));
+        prefix::longest(path, entries).map(|(prefix, _)| prefix)
+    }
+}
+
+/// Appends exchanges to one NDJSON file per prefix.
+struct Recorder {
+    settings: Settings,
+    dir: PathBuf,
+    /// Keeps lines of concurrent requests from interleaving.
+    writing: Mutex<()>,
+}
+
+impl Recorder {
+    fn file(&self, prefix: &str) -> PathBuf {
+        let name = match prefix.trim_matches('/') {
+            "" => "all".to_string(),
+            prefix => prefix.replace('/', "_"),
+        };
+        self.dir.join(format!("{}.ndjson", name))
+    }
+
+    async fn append(&self, prefix: &str, exchange: &Exchange) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(exchange)?;
+        line.push(b'\n');
+        let _writing = self.writing.lock().await;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.file(prefix))
+            .await?
+            .write_all(&line)
+            .await
+    }
+}
+
+/// The request half of an exchange, captured before the body is handed to the route.
+struct Captured(Option<(String, Body, bool)>);
+
+/// Records requests to the configured prefixes along with their responses, so a wrong answer
+/// reported by a validator or client can be replayed with [`Replay`].
+///
+/// Off unless the `record` table of the Rocket config lists prefixes. Rocket only lets fairings
+/// peek at the first 512 bytes of a request, so larger request bodies are recorded truncated and
+/// their exchanges cannot be replayed. Streamed response bodies are left out rather than held
+/// back from the client. Credentials are never recorded.
+pub struct Recording;
+
+#[rocket::async_trait]
+impl Fairing for Recording {
+    fn info(&self) -> Info {
+        Info {
+            name: "Recording",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let settings = match rocket.figment().extract_inner::<Settings>("record") {
+            Ok(settings) => settings,
+            Err(e) if e.missing() => return Ok(rocket),
+            Err(e) => {
+                error!("Invalid record config: {}", e);
+                return Err(rocket);
+            }
+        };
+        let dir = settings.dir.relative();
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            error!(?dir, "Could not create recording directory: {}", e);
+            return Err(rocket);
+        }
+        warn!(?dir, prefixes = ?settings.prefixes, "Recording requests");
+        Ok(rocket.manage(Recorder {
+            settings,
+            dir,
+            writing: Mutex::new(()),
+        }))
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, data: &mut Data<'_>) {
+        let Some(recorder) = request.rocket().state::<Recorder>() else {
+            return;
+        };
+        let Some(prefix) = recorder.settings.prefix(request.uri().path().as_str()) else {
+            return;
+        };
+        if request.headers().contains(REPLAY_HEADER) {
+            return;
+        }
+        let peeked = data.peek(recorder.settings.max_body).await.to_vec();
+        let truncated = !data.peek_complete();
+        let captured = Captured(Some((prefix.to_string(), Body::new(peeked), truncated)));
+        request.local_cache(|| captured);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Captured(Some((prefix, body, truncated))) = request.local_cache(|| Captured(None))
+        else {
+            return;
+        };
+        let Some(recorder) = request.rocket().state::<Recorder>() else {
+            return;
+        };
+        // Upgraded connections have no body to take
+        if response.status() == Status::SwitchingProtocols {
+            return;
+        }
+        let response_body = match response.body().preset_size() {
+            // Reading a stream to its end would hold it back until then, if it ends at all
+            None => None,
+            Some(size) if size > recorder.settings.max_body => None,
+            Some(_) => match response.body_mut().to_bytes().await {
+                Ok(bytes) => {
+                    response.set_sized_body(bytes.len(), std::io::Cursor::new(bytes.clone()));
+                    Some(Body::new(bytes))
+                }
+                Err(e) => {
+                    error!("Could not read response body to record: {}", e);
+                    return;
+                }
+            },
+        };
+        let exchange = Exchange {
+            request_id: RequestId::of(request).to_string(),
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_millis()),
+            method: request.method().to_string(),
+            uri: request.uri().to_string(),
+            headers: headers(request.headers()),
+            body: Some(body.clone()),
+            truncated: *truncated,
+            status: response.status().code,
+            response_headers: headers(response.headers()),
+            response_body,
+        };
+        if let Err(e) = recorder.append(prefix, &exchange).await {
+            error!(prefix, "Could not record exchange: {}", e);
+        }
+    }
+}
+
+/// Why a replayed exchange did not match its recording.
+#[derive(Debug)]
+enum Mismatch {
+    Status(u16, u16),
+    ContentType(Option<String>, Option<String>),
+    Body(String, String),
+}
+
+/// Re-sends recorded requests to a running instance and reports the responses that changed.
+///
+/// Bodies that are JSON on both sides are compared as values, so their formatting and the order
+/// of object keys do not matter. Only status, `Content-Type` and body are compared, since the
+/// other headers carry request IDs, rate limits and the like.
+pub struct Replay {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+/// How many exchanges of a recording matched.
+#[derive(Debug, Default)]
+pub struct Tally {
+    pub matched: usize,
+    pub differed: usize,
+    pub skipped: usize,
+}
+
+impl Replay {
+    /// Sends requests to `base_url`, with `api_key` as the bearer token since keys are not
+    /// recorded.
+    pub fn new(base_url: &str, api_key: Option<String>) -> Self {
+        Replay {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    /// Replays every exchange in the NDJSON file at `path`, printing the ones that differ.
+    pub async fn run(&self, path: &Path) -> Result<Tally, Box<dyn Error + Send + Sync>> {
+        // Read up front, the instance may be recording to the same file
+        let recording = rocket::tokio::fs::read_to_string(path).await?;
+        let mut tally = Tally::default();
+        for (number, line) in (1..).zip(recording.lines()) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let exchange = serde_json::from_str::<Exchange>(line)
+                .map_err(|e| format!("{}:{}: {}", path.display(), number, e))?;
+            let title = format!(
+                "{}:{} {} {} ({})",
+                path.display(),
+                number,
+                exchange.method,
+                exchange.uri,
+                exchange.request_id
+            );
+            if exchange.truncated || exchange.body.is_none() {
+                println!("SKIP {}: request body was too large to record", title);
+                tally.skipped += 1;
+                continue;
+            }
+            let mismatches = self.replay(&exchange).await?;
+            if mismatches.is_empty() {
+                tally.matched += 1;
+                continue;
+            }
+            tally.differed += 1;
+            println!("DIFF {}", title);
+            for mismatch in mismatches {
+                match mismatch {
+                    Mismatch::Status(recorded, replayed) => {
+                        println!("  status: {} -> {}", recorded, replayed)
+                    }
+                    Mismatch::ContentType(recorded, replayed) => {
+                        println!("  content-type: {:?} -> {:?}", recorded, replayed)
+                    }
+                    Mismatch::Body(recorded, replayed) => {
+                        println!("  body:");
+                        for line in diff(&recorded, &replayed) {
+                            println!("    {}", line);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(tally)
+    }
+
+    async fn replay(
+        &self,
+        exchange: &Exchange,
+    ) -> Result<Vec<Mismatch>, Box<dyn Error + Send + Sync>> {
+        let method = reqwest::Method::from_bytes(exchange.method.as_bytes())?;
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, exchange.uri));
+        for (name, value) in &exchange.headers {
+            if !CLIENT_HEADERS
+                .iter()
+                .any(|header| name.eq_ignore_ascii_case(header))
+            {
+                request = request.header(name, value);
+            }
+        }
+        request = request.header(REPLAY_HEADER, &exchange.request_id);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        if let Some(body) = &exchange.body {
+            request = request.body(body.bytes()?);
+        }
+        let response = request.send().await?;
+
+        let mut mismatches = Vec::new();
+        let status = response.status().as_u16();
+        if status != exchange.status {
+            mismatches.push(Mismatch::Status(exchange.status, status));
+        }
+        let recorded_type = header(&exchange.response_headers, "Content-Type");
+        let replayed_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        if recorded_type != replayed_type {
+            mismatches.push(Mismatch::ContentType(recorded_type, replayed_type));
+        }
+        let replayed_body = Body::new(response.bytes().await?.to_vec());
+        if let Some(recorded_body) = &exchange.response_body {
+            let (recorded, replayed) = (comparable(recorded_body), comparable(&replayed_body));
+            if recorded != replayed {
+                mismatches.push(Mismatch::Body(recorded, replayed));
+            }
+        }
+        Ok(mismatches)
+    }
+}
+
+fn header(headers: &[(String, String)], name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.clone())
+}
+
+/// The body as text to compare and diff, with JSON pretty printed.
+fn comparable(body: &Body) -> String {
+    match body {
+        Body::Text(text) => match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(value) => serde_json::to_string_pretty(&value).unwrap_or_else(|_| text.clone()),
+            Err(_) => text.clone(),
+        },
+        Body::Base64(encoded) => encoded.clone(),
+    }
+}
+
+/// Lines of `recorded` that are gone prefixed with `-`, those of `replayed` that are new with
+/// `+`, based on their longest common subsequence.
+fn diff(recorded: &str, replayed: &str) -> Vec<String> {
+    let recorded = recorded.lines().collect::<Vec<_>>();
+    let replayed = replayed.lines().collect::<Vec<_>>();
+    // common[i][j] is the length of the longest common subsequence of recorded[i..] and replayed[j..]
+    let mut common = vec![vec![0usize; replayed.len() + 1]; recorded.len() + 1];
+    for i in (0..recorded.len()).rev() {
+        for j in (0..replayed.len()).rev() {
+            common[i][j] = if recorded[i] == replayed[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < recorded.len() || j < replayed.len() {
+        if i < recorded.len() && j < replayed.len() && recorded[i] == replayed[j] {
+            lines.push(format!("  {}", recorded[i]));
+            i += 1;
+            j += 1;
+        } else if i < recorded.len()
+            && (j == replayed.len() || common[i + 1][j] >= common[i][j + 1])
+        {
+            lines.push(format!("- {}", recorded[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", replayed[j]));
+            j += 1;
+        }
+    }
+    lines
+}
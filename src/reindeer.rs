@@ -0,0 +1,494 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use rocket::fairing::{AdHoc, Fairing};
+use rocket::serde::json::{Json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{delete, get, post, put, routes, Build, Rocket, Route, State};
+use schemars::JsonSchema;
+use sqlx::{query, query_as, query_scalar, Executor, FromRow, QueryBuilder};
+use tracing::error;
+use ulid::Ulid;
+
+use crate::auth::{scope, Authorized, Scope};
+use crate::day::Day;
+use crate::day4::{self, Category, Checked, ContestParticipant, Day4, Reindeer, Validated};
+use crate::error::{ApiError, FieldError};
+use crate::infrastructure::{with_pool, Database, Infrastructure};
+use crate::openapi::{Body, Operation};
+
+/// Creates the tables unless they exist, so the roster outlives restarts.
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS reindeer (
+  name VARCHAR(100) PRIMARY KEY,
+  strength INT NOT NULL,
+  speed REAL NOT NULL,
+  height INT NOT NULL,
+  antler_width INT NOT NULL,
+  snow_magic_power INT NOT NULL,
+  favorite_food VARCHAR(100) NOT NULL,
+  candies_eaten_yesterday INT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS contest_results (
+  contest VARCHAR(26) NOT NULL,
+  held_at TIMESTAMPTZ NOT NULL,
+  position INT NOT NULL,
+  category VARCHAR(100) NOT NULL,
+  field VARCHAR(50) NOT NULL,
+  winner VARCHAR(100) NOT NULL,
+  message TEXT NOT NULL,
+  PRIMARY KEY (contest, position)
+);";
+
+const DROP: &str = "DROP TABLE IF EXISTS contest_results;
+DROP TABLE IF EXISTS reindeer;";
+
+const MAX_LIMIT: i64 = 100;
+/// Longest names of reindeer, foods and categories the tables hold, in characters.
+const MAX_TEXT: usize = 100;
+
+/// A reindeer of the roster, in the shape of a contest participant.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, FromRow)]
+#[schemars(rename = "RegisteredReindeer")]
+struct RegisteredReindeer {
+    name: String,
+    strength: i32,
+    speed: f32,
+    height: i32,
+    antler_width: i32,
+    snow_magic_power: i32,
+    favorite_food: String,
+    #[serde(rename = "cAnD13s_3ATeN-yesT3rdAy")]
+    candies_eaten_yesterday: i32,
+}
+
+impl RegisteredReindeer {
+    fn participant(&self) -> ContestParticipant<'_> {
+        ContestParticipant {
+            reindeer: Reindeer {
+                name: Cow::Borrowed(&self.name),
+                strength: self.strength,
+            },
+            speed: self.speed,
+            height: self.height,
+            antler_width: self.antler_width,
+            snow_magic_power: self.snow_magic_power,
+            favorite_food: Cow::Borrowed(&self.favorite_food),
+            candies_eaten_yesterday: self.candies_eaten_yesterday,
+        }
+    }
+}
+
+impl Checked for RegisteredReindeer {
+    fn check(value: &Value) -> Vec<FieldError> {
+        let mut errors = day4::check_participant(value);
+        for field in ["name", "favorite_food"] {
+            if value[field]
+                .as_str()
+                .is_some_and(|text| text.chars().count() > MAX_TEXT)
+            {
+                errors.push(FieldError {
+                    index: None,
+                    field: Some(field.to_string()),
+                    reason: format!("must be at most {} characters long", MAX_TEXT),
+                });
+            }
+        }
+        errors
+    }
+}
+
+/// The categories to hold a contest over the roster in, the original ones by default.
+#[derive(Deserialize, JsonSchema)]
+struct RosterContest {
+    #[serde(default = "Category::defaults")]
+    categories: Vec<Category>,
+}
+
+impl Checked for RosterContest {
+    fn check(value: &Value) -> Vec<FieldError> {
+        let mut errors = day4::check_contest_categories(value);
+        let categories = value["categories"].as_array().into_iter().flatten();
+        for (index, category) in categories.enumerate() {
+            if category["name"]
+                .as_str()
+                .is_some_and(|name| name.chars().count() > MAX_TEXT)
+            {
+                errors.push(FieldError {
+                    index: Some(index),
+                    field: Some("categories".to_string()),
+                    reason: format!("name must be at most {} characters long", MAX_TEXT),
+                });
+            }
+        }
+        errors
+    }
+}
+
+#[derive(Serialize, JsonSchema, Debug, FromRow)]
+struct ContestResult {
+    category: String,
+    field: String,
+    winner: String,
+    message: String,
+}
+
+/// A contest held over the roster, with the winner of every category.
+#[derive(Serialize, JsonSchema, Debug)]
+struct HeldContest {
+    id: String,
+    #[schemars(with = "String")]
+    held_at: DateTime<Utc>,
+    results: Vec<ContestResult>,
+}
+
+#[derive(FromRow)]
+struct ContestResultRow {
+    contest: String,
+    held_at: DateTime<Utc>,
+    #[sqlx(flatten)]
+    result: ContestResult,
+}
+
+#[derive(Serialize, JsonSchema, Debug, FromRow)]
+struct Standing {
+    name: String,
+    wins: i64,
+}
+
+fn database_error(context: &'static str) -> impl FnOnce(sqlx::Error) -> ApiError {
+    move |source| ApiError::Database { context, source }
+}
+
+fn limit(limit: Option<&str>) -> Result<i64, ApiError> {
+    match limit.map(str::parse::<i64>).unwrap_or(Ok(10)) {
+        Ok(limit @ 1..=MAX_LIMIT) => Ok(limit),
+        _ => Err(ApiError::BadRequest(format!(
+            "The limit must be between 1 and {}",
+            MAX_LIMIT
+        ))),
+    }
+}
+
+#[post("/reset")]
+async fn reset(
+    _authorized: Authorized<scope::Admin>,
+    infrastructure: &State<Infrastructure>,
+) -> Result<(), ApiError> {
+    ReindeerRegistry::reset(&infrastructure.database).await
+}
+
+#[get("/reindeer")]
+async fn roster(
+    infrastructure: &State<Infrastructure>,
+) -> Result<Json<Vec<RegisteredReindeer>>, ApiError> {
+    ReindeerRegistry::roster(&infrastructure.database)
+        .await
+        .map(Json)
+}
+
+#[get("/reindeer/<name>")]
+async fn get_reindeer(
+    name: &str,
+    infrastructure: &State<Infrastructure>,
+) -> Result<Json<RegisteredReindeer>, ApiError> {
+    with_pool!(&infrastructure.database, |pool| query_as::<
+        _,
+        RegisteredReindeer,
+    >(
+        "SELECT * FROM reindeer WHERE name = $1"
+    )
+    .bind(name)
+    .fetch_optional(pool)
+    .await)
+    .map_err(database_error("Could not query reindeer"))?
+    .map(Json)
+    .ok_or_else(|| ApiError::NotFound(format!("No reindeer named {} is registered", name)))
+}
+
+#[put("/reindeer/<name>", data = "<reindeer>")]
+async fn put_reindeer(
+    _authorized: Authorized<scope::Write>,
+    name: &str,
+    reindeer: Result<Validated<RegisteredReindeer>, ApiError>,
+    infrastructure: &State<Infrastructure>,
+) -> Result<Json<RegisteredReindeer>, ApiError> {
+    let Validated(reindeer) = reindeer?;
+    if reindeer.name != name {
+        return Err(ApiError::BadRequest(format!(
+            "The reindeer is named {}, but was put as {}",
+            reindeer.name, name
+        )));
+    }
+    with_pool!(&infrastructure.database, |pool| query(
+        "INSERT INTO reindeer (name, strength, speed, height, antler_width, snow_magic_power, \
+        favorite_food, candies_eaten_yesterday) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT (name) DO UPDATE SET strength = excluded.strength, speed = excluded.speed, \
+        height = excluded.height, antler_width = excluded.antler_width, \
+        snow_magic_power = excluded.snow_magic_power, favorite_food = excluded.favorite_food, \
+        candies_eaten_yesterday = excluded.candies_eaten_yesterday;"
+    )
+    .bind(&reindeer.name)
+    .bind(reindeer.strength)
+    .bind(reindeer.speed)
+    .bind(reindeer.height)
+    .bind(reindeer.antler_width)
+    .bind(reindeer.snow_magic_power)
+    .bind(&reindeer.favorite_food)
+    .bind(reindeer.candies_eaten_yesterday)
+    .execute(pool)
+    .await
+    .map(|_| ()))
+    .map_err(database_error("Could not store reindeer"))?;
+    Ok(Json(reindeer))
+}
+
+#[delete("/reindeer/<name>")]
+async fn delete_reindeer(
+    _authorized: Authorized<scope::Write>,
+    name: &str,
+    infrastructure: &State<Infrastructure>,
+) -> Result<(), ApiError> {
+    let deleted = with_pool!(&infrastructure.database, |pool| query(
+        "DELETE FROM reindeer WHERE name = $1"
+    )
+    .bind(name)
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected()))
+    .map_err(database_error("Could not delete reindeer"))?;
+    match deleted {
+        0 => Err(ApiError::NotFound(format!(
+            "No reindeer named {} is registered",
+            name
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[get("/strength")]
+async fn strength(infrastructure: &State<Infrastructure>) -> Result<String, ApiError> {
+    with_pool!(&infrastructure.database, |pool| query_scalar::<_, i64>(
+        "SELECT CAST(COALESCE(SUM(strength), 0) AS BIGINT) FROM reindeer"
+    )
+    .fetch_one(pool)
+    .await)
+    .map(|strength| strength.to_string())
+    .map_err(database_error("Could not sum strength"))
+}
+
+#[post("/contest", data = "<contest>")]
+async fn contest(
+    _authorized: Authorized<scope::Write>,
+    contest: Result<Validated<RosterContest>, ApiError>,
+    infrastructure: &State<Infrastructure>,
+) -> Result<Json<HeldContest>, ApiError> {
+    let Validated(contest) = contest?;
+    let roster = ReindeerRegistry::roster(&infrastructure.database).await?;
+    let participants = roster
+        .iter()
+        .map(RegisteredReindeer::participant)
+        .collect::<Vec<_>>();
+    Day4::check(&participants, &contest.categories)?;
+    let held = HeldContest {
+        id: Ulid::new().to_string(),
+        // As precise as Postgres stores it, so the history shows the same time
+        held_at: Utc::now().trunc_subsecs(6),
+        results: Day4::winners(&participants, &contest.categories)?
+            .into_iter()
+            .map(|win| ContestResult {
+                category: win.category.name.clone(),
+                field: win.category.field.name().to_string(),
+                winner: win.winner.reindeer.name.to_string(),
+                message: win.message,
+            })
+            .collect(),
+    };
+    with_pool!(&infrastructure.database, |pool| {
+        let mut query = QueryBuilder::new(
+            "INSERT INTO contest_results \
+            (contest, held_at, position, category, field, winner, message) ",
+        );
+        query.push_values(
+            held.results.iter().enumerate(),
+            |mut b, (position, result)| {
+                b.push_bind(&held.id)
+                    .push_bind(held.held_at)
+                    .push_bind(position as i32)
+                    .push_bind(&result.category)
+                    .push_bind(&result.field)
+                    .push_bind(&result.winner)
+                    .push_bind(&result.message);
+            },
+        );
+        query.build().execute(pool).await.map(|_| ())
+    })
+    .map_err(database_error("Could not store contest results"))?;
+    Ok(Json(held))
+}
+
+/// The most recent contests first.
+#[get("/contests?<limit>")]
+async fn contests(
+    limit: Option<&str>,
+    infrastructure: &State<Infrastructure>,
+) -> Result<Json<Vec<HeldContest>>, ApiError> {
+    let limit = self::limit(limit)?;
+    let rows = with_pool!(&infrastructure.database, |pool| query_as::<
+        _,
+        ContestResultRow,
+    >(
+        "SELECT contest, held_at, category, field, winner, message FROM contest_results
+WHERE contest IN (
+  SELECT contest FROM contest_results GROUP BY contest ORDER BY MAX(held_at) DESC, contest DESC \
+        LIMIT $1
+)
+ORDER BY held_at DESC, contest DESC, position ASC;"
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await)
+    .map_err(database_error("Could not query contests"))?;
+    let mut contests: Vec<HeldContest> = Vec::new();
+    for row in rows {
+        match contests.last_mut() {
+            Some(contest) if contest.id == row.contest => contest.results.push(row.result),
+            _ => contests.push(HeldContest {
+                id: row.contest,
+                held_at: row.held_at,
+                results: vec![row.result],
+            }),
+        }
+    }
+    Ok(Json(contests))
+}
+
+/// Reindeer by their number of wins, of several with as many in the order of their names.
+#[get("/leaderboard?<category>&<limit>")]
+async fn leaderboard(
+    category: Option<&str>,
+    limit: Option<&str>,
+    infrastructure: &State<Infrastructure>,
+) -> Result<Json<Vec<Standing>>, ApiError> {
+    let limit = self::limit(limit)?;
+    with_pool!(&infrastructure.database, |pool| query_as::<_, Standing>(
+        "SELECT winner AS name, CAST(COUNT(*) AS BIGINT) AS wins FROM contest_results
+WHERE $1 IS NULL OR category = $1
+GROUP BY winner
+ORDER BY wins DESC, name ASC
+LIMIT $2;"
+    )
+    .bind(category)
+    .bind(limit)
+    .fetch_all(pool)
+    .await)
+    .map(Json)
+    .map_err(database_error("Could not query leaderboard"))
+}
+
+pub struct ReindeerRegistry {}
+
+impl Day for ReindeerRegistry {
+    const NAME: &'static str = "reindeer";
+    const PATH: &'static str = "/4/registry";
+    const DESCRIPTION: &'static str = "Reindeer roster and contest history stored in SQL";
+    const SCHEMA: Option<&'static str> = Some(SCHEMA);
+
+    fn routes() -> Vec<Route> {
+        routes![
+            reset,
+            roster,
+            get_reindeer,
+            put_reindeer,
+            delete_reindeer,
+            strength,
+            contest,
+            contests,
+            leaderboard
+        ]
+    }
+
+    fn manage(rocket: Rocket<Build>) -> Rocket<Build> {
+        rocket.attach(Self::fairing())
+    }
+
+    fn docs() -> Vec<Operation> {
+        vec![
+            Operation::new("reset", "Drops the roster and the contest history")
+                .requires(Scope::Admin),
+            Operation::new("roster", "All registered reindeer, by name")
+                .response(Body::json::<Vec<RegisteredReindeer>>()),
+            Operation::new("get_reindeer", "The registered reindeer of that name")
+                .response(Body::json::<RegisteredReindeer>()),
+            Operation::new(
+                "put_reindeer",
+                "Registers the reindeer, or replaces the one of the same name",
+            )
+            .request(Body::json::<RegisteredReindeer>())
+            .response(Body::json::<RegisteredReindeer>())
+            .requires(Scope::Write),
+            Operation::new("delete_reindeer", "Removes the reindeer from the roster")
+                .requires(Scope::Write),
+            Operation::new("strength", "Sum of the strength of the roster").response(Body::Text),
+            Operation::new(
+                "contest",
+                "Holds a contest over the roster in the given `categories`, the original ones by \
+                default, and stores its winners",
+            )
+            .request(Body::json::<RosterContest>())
+            .response(Body::json::<HeldContest>())
+            .requires(Scope::Write),
+            Operation::new("contests", "The most recent contests, 10 by default")
+                .parameter::<i64>("limit")
+                .response(Body::json::<Vec<HeldContest>>()),
+            Operation::new(
+                "leaderboard",
+                "Reindeer by their wins, in all categories or the given one, ties by name",
+            )
+            .parameter::<String>("category")
+            .parameter::<i64>("limit")
+            .response(Body::json::<Vec<Standing>>()),
+        ]
+    }
+}
+
+impl ReindeerRegistry {
+    /// Drops and recreates the tables of the registry.
+    pub async fn reset(database: &Database) -> Result<(), ApiError> {
+        with_pool!(database, |pool| pool
+            .execute(format!("{}\n{}", DROP, SCHEMA).as_str())
+            .await
+            .map(|_| ()))
+        .map_err(database_error("Could not reset database"))
+    }
+
+    async fn roster(database: &Database) -> Result<Vec<RegisteredReindeer>, ApiError> {
+        with_pool!(database, |pool| query_as::<_, RegisteredReindeer>(
+            "SELECT * FROM reindeer ORDER BY name ASC"
+        )
+        .fetch_all(pool)
+        .await)
+        .map_err(database_error("Could not query roster"))
+    }
+
+    /// Creates the tables on launch, failing it if the database cannot.
+    fn fairing() -> impl Fairing {
+        AdHoc::try_on_ignite("Reindeer registry", |rocket| async {
+            let created = match rocket.state::<Infrastructure>() {
+                Some(infrastructure) => with_pool!(&infrastructure.database, |pool| pool
+                    .execute(SCHEMA)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())),
+                None => Err("Infrastructure is not managed".to_string()),
+            };
+            match created {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    error!("{}, the reindeer registry needs its tables", e);
+                    Err(rocket)
+                }
+            }
+        })
+    }
+}
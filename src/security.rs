@@ -0,0 +1,193 @@
+use std::io::Cursor;
+
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use rocket::response::{self, Responder};
+use rocket::serde::Deserialize;
+use rocket::shield::{Frame, Hsts, Referrer, Shield};
+use rocket::{Build, Request, Response, Rocket};
+use tracing::{debug, error};
+
+const CONTENT_SECURITY_POLICY: &str = "Content-Security-Policy";
+
+/// Which other origins may call the API from a browser, read from the `cors` table of the Rocket
+/// config, e.g. `ROCKET_CORS={allowed_origins=["https://example.com"]}`.
+///
+/// No origin is allowed by default, `*` allows any.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde", default)]
+struct Cors {
+    allowed_origins: Vec<String>,
+    allowed_methods: Vec<String>,
+    allowed_headers: Vec<String>,
+    exposed_headers: Vec<String>,
+    allow_credentials: bool,
+    /// How many seconds browsers may cache the answer to a preflight request.
+    max_age: u32,
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        Cors {
+            allowed_origins: Vec::new(),
+            allowed_methods: strings(&["GET", "POST", "PUT", "PATCH", "DELETE"]),
+            allowed_headers: strings(&["Content-Type", "Authorization", "X-Api-Key"]),
+            exposed_headers: strings(&[
+                "X-Request-Id",
+                "X-RateLimit-Limit",
+                "X-RateLimit-Remaining",
+                "Retry-After",
+            ]),
+            allow_credentials: false,
+            max_age: 86400,
+        }
+    }
+}
+
+impl Cors {
+    fn allows(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+    }
+
+    /// Browsers refuse credentials for any origin, so those get the origin echoed.
+    fn allow_origin<'a>(&self, origin: &'a str) -> &'a str {
+        let any = self.allowed_origins.iter().any(|allowed| allowed == "*");
+        if any && !self.allow_credentials {
+            "*"
+        } else {
+            origin
+        }
+    }
+
+    fn is_preflight(request: &Request<'_>) -> bool {
+        request.method() == Method::Options
+            && request.headers().contains("Access-Control-Request-Method")
+    }
+
+    fn apply(&self, request: &Request<'_>, response: &mut Response<'_>) {
+        let Some(origin) = request.headers().get_one("Origin") else {
+            return;
+        };
+        if !self.allows(origin) {
+            return;
+        }
+        response.set_header(Header::new(
+            "Access-Control-Allow-Origin",
+            self.allow_origin(origin).to_string(),
+        ));
+        response.adjoin_header(Header::new("Vary", "Origin"));
+        if self.allow_credentials {
+            response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        }
+        if !Self::is_preflight(request) {
+            response.set_header(Header::new(
+                "Access-Control-Expose-Headers",
+                self.exposed_headers.join(", "),
+            ));
+            return;
+        }
+        // No route answers OPTIONS, so this replaces whatever the catchers made of it
+        response.set_status(Status::NoContent);
+        response.remove_header("Content-Type");
+        response.set_sized_body(0, Cursor::new(""));
+        response.set_header(Header::new(
+            "Access-Control-Allow-Methods",
+            self.allowed_methods.join(", "),
+        ));
+        response.set_header(Header::new(
+            "Access-Control-Allow-Headers",
+            self.allowed_headers.join(", "),
+        ));
+        response.set_header(Header::new(
+            "Access-Control-Max-Age",
+            self.max_age.to_string(),
+        ));
+    }
+}
+
+/// A response with its own `Content-Security-Policy`, instead of the one [`SecurityHeaders`]
+/// adds to every response.
+pub struct WithPolicy<R>(pub &'static str, pub R);
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for WithPolicy<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        let mut response = self.1.respond_to(request)?;
+        response.set_header(Header::new(CONTENT_SECURITY_POLICY, self.0));
+        Ok(response)
+    }
+}
+
+/// What responses may load when rendered by a browser, read from `content_security_policy` in the
+/// Rocket config. Nothing by default, since all but a few routes answer with data.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Policy {
+    #[serde(default = "Policy::default_content_security_policy")]
+    content_security_policy: String,
+}
+
+impl Policy {
+    fn default_content_security_policy() -> String {
+        "default-src 'none'; frame-ancestors 'none'".to_string()
+    }
+}
+
+/// CORS and a default `Content-Security-Policy` for every response, next to the headers of the
+/// [`shield`].
+pub struct SecurityHeaders;
+
+#[rocket::async_trait]
+impl Fairing for SecurityHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Security headers",
+            kind: Kind::Ignite | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let cors = match rocket.figment().extract_inner::<Cors>("cors") {
+            Ok(cors) => cors,
+            Err(e) if e.missing() => Cors::default(),
+            Err(e) => {
+                error!("Invalid cors config: {}", e);
+                return Err(rocket);
+            }
+        };
+        let policy = match rocket.figment().extract::<Policy>() {
+            Ok(policy) => policy,
+            Err(e) => {
+                error!("Invalid content_security_policy config: {}", e);
+                return Err(rocket);
+            }
+        };
+        debug!(?cors, ?policy, "Configured security headers");
+        Ok(rocket.manage(cors).manage(policy))
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let rocket = request.rocket();
+        if let Some(cors) = rocket.state::<Cors>() {
+            cors.apply(request, response);
+        }
+        if let Some(policy) = rocket.state::<Policy>() {
+            if !response.headers().contains(CONTENT_SECURITY_POLICY) {
+                response.set_header(Header::new(
+                    CONTENT_SECURITY_POLICY,
+                    policy.content_security_policy.clone(),
+                ));
+            }
+        }
+    }
+}
+
+/// Rocket's defaults plus HSTS, no referrers and no framing at all.
+pub fn shield() -> Shield {
+    Shield::default()
+        .enable(Hsts::default())
+        .enable(Referrer::NoReferrer)
+        .enable(Frame::Deny)
+}
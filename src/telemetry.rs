@@ -0,0 +1,129 @@
+use std::time::{Duration, Instant};
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
+use ulid::Ulid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Identifies a request across our logs, responses and outbound calls.
+///
+/// Taken from the incoming `X-Request-Id` header if it looks sane, generated otherwise.
+#[derive(Debug, Clone)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn of<'r>(request: &'r Request<'_>) -> &'r str {
+        &request
+            .local_cache(|| RequestId::from_header(request).unwrap_or_default())
+            .0
+    }
+
+    fn from_header(request: &Request<'_>) -> Option<RequestId> {
+        request
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .filter(|id| !id.is_empty() && id.len() <= 128)
+            .filter(|id| id.chars().all(|c| c.is_ascii_graphic()))
+            .map(|id| RequestId(id.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        RequestId(Ulid::new().to_string())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId(RequestId::of(request).to_string()))
+    }
+}
+
+struct RequestStart(Instant);
+
+/// Time passed since the request arrived.
+pub fn elapsed(request: &Request<'_>) -> Duration {
+    request
+        .local_cache(|| RequestStart(Instant::now()))
+        .0
+        .elapsed()
+}
+
+/// Assigns every request an ID and logs method, route, status and latency once it is answered.
+pub struct RequestTracing;
+
+#[rocket::async_trait]
+impl Fairing for RequestTracing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request tracing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+        RequestId::of(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let request_id = RequestId::of(request);
+        let latency = elapsed(request);
+        let route = request.route().map(|route| route.uri.as_str());
+        let status = response.status();
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        macro_rules! log {
+            ($level:ident) => {
+                $level!(
+                    request_id,
+                    method = %request.method(),
+                    route,
+                    uri = %request.uri(),
+                    status = status.code,
+                    latency_ms,
+                    "{} {} {}",
+                    request.method(),
+                    request.uri(),
+                    status
+                )
+            };
+        }
+        if status.class().is_server_error() {
+            log!(error)
+        } else if status.class().is_client_error() {
+            log!(warn)
+        } else {
+            log!(info)
+        }
+        response.set_header(Header::new(REQUEST_ID_HEADER, request_id.to_string()));
+    }
+}
+
+/// Logs as JSON to stdout, filtered by `RUST_LOG`.
+///
+/// By default, rocket's own per-request messages are left out since [`RequestTracing`] covers them.
+///
+/// Only needed outside of Shuttle, whose runtime installs its own subscriber.
+pub fn init() {
+    tracing_subscriber::fmt()
+        .json()
+        .with_current_span(true)
+        .with_env_filter(
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new("info,rocket::server=warn")),
+        )
+        .init();
+}
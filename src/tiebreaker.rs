@@ -1,23 +1,147 @@
-use std::str::FromStr;
+use std::cell::Cell;
+use std::fmt::Display;
 
-use josekit::jwe::{deserialize_json, RSA_OAEP_256};
-use josekit::jwk::Jwk;
+use josekit::jwe::alg::rsaes::RsaesJweDecrypter;
+use josekit::jwe::{deserialize_json_with_selector, JweDecrypter, RSA_OAEP_256};
+use josekit::jwk::{Jwk, JwkSet};
+use josekit::Value;
+use reqwest::Client;
+use rocket::fairing::{AdHoc, Fairing};
 use rocket::serde::json::serde_json;
 use rocket::serde::{Deserialize, Serialize};
-use rocket::{get, routes, Route};
-
-const KEY: &str = r#"{
-  "kty": "RSA",
-  "n": "tX4yurmjaH70PEgrVcrq6syvAzWCp3EvLmoYeq4JSQruT3r0fsEN_3iRNQ13VALZSL_k9xidlYEDqhNN6owui3uql6L8UrhmhhOeNOOYI4YOpTa9Yda_yuYFii6o_NrOpHv4LmrMzLzCX7kPW3j4GNiS7vYkwGI0n1mtVGqpYs9jic4GR3Be-kBMgNpZanJk9OA2LKf1cyh2n5LkU9lO15ZszvKfA9u_08A5s62b9_MhjEVlmENUWXGJzZtx-pZMWZwZFjV2KrEoCY3BykmwSSNyhdxN1NKp-l3_plOLop96G621k8tabctHbS565BpmDiKT5rNymXDZWpiYod6gpQ",
-  "e": "AQAB",
-  "d": "LYTySjzHDC1TKk9bdxAGrU8a0e44z7AmijiX3SULNSOls49-BNB8p0dg-_JdrFdukb13OrYUx-tstNpUn2_7OIaSuadqK4EOTbBb7J3siXRU9gDtrL1EqynX19luDsT-MOjazSGCLhNlmMZ8YI_NgcXHzGE1xH6c_h5qx5Jc22gGP6XRsQLsDioUEJOigmCsma97ZO_x2P9ySm19n1cdx4_FWgUSlchHuObWD7a0ubY4KKDY3DCzF4IRFqVw_qlf7osaiX3-oSPHyaAd4NyM7bhKWDlM2-GBQyYrMItfdRF70LHhAmXDrsvY3CwdgIHuNJIUKbBTJFMHsKhLPWM9vQ",
-  "p": "3Zq-rd7P8zN887yet08kOUvzHh8fA6EaDLA54Rpql1-dVMlJ7RbVWzO6x5pEv8HkJliXjF_NNyhBPJklT2i_76VY9yZlasGqG7qsOFBgPUuKqfNrCmTcrKf_KyWQZdKNqicmjtWTOeHZNS3jpqroct80PNSgDCYBsaWIsp2wgxs",
-  "q": "0amojC5uKSGblMfpyYqZRY4LBvIGXtAG6vz6mTXmr6O-pMXvxKYZqqVO1Xjqsu6SZuMci5iYQ8hmcfsneSW6aXoDOokPAsejD2ayMqnkAFd1fYqFPo9Gmf4C9zkoMiAbxC2RI6ertIMrOEu0peUR4uJsj5wvFDTyNx2Nbnhz5z8",
-  "dp": "Ojy7lafzkF9cnBVaxKPIykH6b1UQanzBAsqhO1Yc4xEeoLSRd_xDL8elc2VIYfiLg8ROd3aJ0NAEbO92TasindEfUzxE9MxWbxkcv2PoFtOuakFtRPsCv2Ea_vTNQOUXk7rcODdKjLCcy4v2wssxcVbVPJNISEkIsu3kwcQNKjc",
-  "dq": "Ci-j8KuQzo7DcEcGJLSHHcn43y2DAbg5ndEMm8TyoDXkXT0AmR04wgGmAtkNDgRpOHZwPJf9Tc2-rGr3T_t1QwqafY2LHSd11Jm4rp1yZlHZc2_3aUKsu26L1lcAjO7ianWMR58tyGdXAjUrYaPvaoZ1n8SGxQSNf___jw5rEyE",
-  "qi": "Q4HW9O6xx699EHeMOnyBUFxewqpEU_kIY5D36PmGeVznmXaRAPXgp3nylvkxjMALQqU4IQ6gLdqjAi-oHk_G83s2vsVc51G1GC5CKleeNpGk_AsEcOgsg_Z1x57wni5p_YmiUPuAkty6KZugCNQLS_6VM4pDs-uyDybmaFEGHWY"
+use rocket::{get, routes, Build, Rocket, Route, State};
+use tracing::{debug, error, instrument};
+
+use crate::day::Day;
+use crate::error::ApiError;
+use crate::infrastructure::{Infrastructure, Secrets};
+use crate::telemetry::{RequestId, REQUEST_ID_HEADER};
+
+/// A JWK set, or a single JWK, of the RSA-OAEP-256 keys the naughty list may be encrypted with.
+const JWKS_SECRET: &str = "TIEBREAKER_JWKS";
+/// Bearer token for the naughty list API.
+const TOKEN_SECRET: &str = "TIEBREAKER_TOKEN";
+
+/// What it takes to fetch, decrypt and answer the naughty list, read from the secrets.
+struct Credentials {
+    /// Decrypters by the `kid` of their key.
+    decrypters: Vec<(Option<String>, RsaesJweDecrypter)>,
+    token: String,
+}
+
+impl Credentials {
+    fn from_secrets(secrets: &Secrets) -> Result<Self, String> {
+        let missing = |name| format!("Missing secret {}", name);
+        let jwks = secrets
+            .get(JWKS_SECRET)
+            .ok_or_else(|| missing(JWKS_SECRET))?;
+        let token = secrets
+            .get(TOKEN_SECRET)
+            .ok_or_else(|| missing(TOKEN_SECRET))?;
+        let invalid = |e: &dyn Display| format!("Invalid secret {}: {}", JWKS_SECRET, e);
+        let jwks = serde_json::from_str::<Value>(jwks).map_err(|e| invalid(&e))?;
+        let keys = match jwks.as_object() {
+            Some(set) if set.contains_key("keys") => JwkSet::from_map(set.clone())
+                .map_err(|e| invalid(&e))?
+                .keys()
+                .into_iter()
+                .cloned()
+                .collect(),
+            Some(key) => vec![Jwk::from_map(key.clone()).map_err(|e| invalid(&e))?],
+            None => return Err(invalid(&"Neither a JWK set nor a JWK")),
+        };
+        if keys.is_empty() {
+            return Err(invalid(&"The JWK set is empty"));
+        }
+        let decrypters = keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                let kid = key.key_id().map(str::to_string);
+                if kid.is_none() && keys.len() > 1 {
+                    return Err(invalid(&format!("Key {} of several has no kid", index)));
+                }
+                let mut decrypter = RSA_OAEP_256
+                    .decrypter_from_jwk(key)
+                    .map_err(|e| invalid(&format!("Key {} cannot decrypt: {}", index, e)))?;
+                // Otherwise josekit insists on the kid, which a lone key does not need
+                if keys.len() == 1 {
+                    decrypter.remove_key_id();
+                }
+                Ok((kid, decrypter))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if token.is_empty() {
+            return Err(format!(
+                "Invalid secret {}: The token is empty",
+                TOKEN_SECRET
+            ));
+        }
+        Ok(Credentials {
+            decrypters,
+            token: token.to_string(),
+        })
+    }
+
+    /// The decrypter for the `kid` of a JWE, or the only one if the JWE names none.
+    fn decrypter(&self, kid: Option<&str>) -> Option<&RsaesJweDecrypter> {
+        match kid {
+            Some(kid) => self
+                .decrypters
+                .iter()
+                .find(|(key_id, _)| key_id.as_deref() == Some(kid)),
+            None if self.decrypters.len() == 1 => self.decrypters.first(),
+            None => None,
+        }
+        .map(|(_, decrypter)| decrypter)
+    }
+
+    fn decrypt(&self, encrypted: &str) -> Result<Vec<u8>, ApiError> {
+        let unknown = Cell::new(None);
+        let decrypted = deserialize_json_with_selector(encrypted, |header| {
+            let decrypter = self.decrypter(header.key_id());
+            if decrypter.is_none() {
+                unknown.set(Some(header.key_id().map(str::to_string)));
+            }
+            Ok(decrypter.map(|decrypter| decrypter as &dyn JweDecrypter))
+        });
+        match (decrypted, unknown.take()) {
+            (Ok((payload, _)), _) => Ok(payload),
+            (Err(_), Some(Some(kid))) => Err(ApiError::internal(
+                "Could not decrypt naughty list",
+                format!("No key with kid {}", kid),
+            )),
+            (Err(_), Some(None)) => Err(ApiError::internal(
+                "Could not decrypt naughty list",
+                "It names no kid, but there are several keys",
+            )),
+            (Err(e), None) => Err(ApiError::internal("Could not decrypt naughty list", e)),
+        }
+    }
+
+    /// Reads the credentials from the secrets, refusing to launch without them.
+    fn fairing() -> impl Fairing {
+        AdHoc::try_on_ignite("Tiebreaker credentials", |rocket| async {
+            let credentials = match rocket.state::<Infrastructure>() {
+                Some(infrastructure) => Self::from_secrets(&infrastructure.secrets),
+                None => Err("Infrastructure is not managed".to_string()),
+            };
+            match credentials {
+                Ok(credentials) => {
+                    debug!(
+                        kids = ?credentials.decrypters.iter().map(|(kid, _)| kid).collect::<Vec<_>>(),
+                        "Loaded tiebreaker keys"
+                    );
+                    Ok(rocket.manage(credentials))
+                }
+                Err(e) => {
+                    error!("{}, the tiebreaker needs its JWKs and bearer token", e);
+                    Err(rocket)
+                }
+            }
+        })
+    }
 }
-"#;
 
 #[derive(Deserialize, Serialize, Debug)]
 struct NaughtyItem {
@@ -33,22 +157,35 @@ struct Response {
 }
 
 #[get("/")]
-async fn get_naughty_list() {
-    // perform a request to https://www.codehunt.rs/api/naughty with bearer auth
-    let key = Jwk::from_bytes(KEY.as_bytes()).unwrap();
-    let client = reqwest::Client::new();
-    let response = client
+#[instrument(skip_all, fields(request_id = request_id.as_str()))]
+async fn get_naughty_list(
+    request_id: RequestId,
+    client: &State<Client>,
+    credentials: &State<Credentials>,
+) -> Result<(), ApiError> {
+    let encrypted = client
         .get("https://www.codehunt.rs/api/naughty")
-        .header("Authorization", "Bearer Lb7bB6PyL1kP0hU2")
+        .bearer_auth(&credentials.token)
+        .header(REQUEST_ID_HEADER, request_id.as_str())
         .send()
         .await
-        .unwrap();
-    let encrypted = response.text().await.unwrap();
-    let decrypter = RSA_OAEP_256.decrypter_from_jwk(&key).unwrap();
-    let (payload, _) = deserialize_json(&encrypted, &decrypter).unwrap();
-    let payload = String::from_utf8(payload).unwrap();
-    let naughty_list = serde_json::from_str::<Vec<NaughtyItem>>(&payload).unwrap();
-    println!("Naughty list: {:?}", payload);
+        .and_then(|response| response.error_for_status())
+        .map_err(|source| ApiError::Upstream {
+            context: "Could not fetch naughty list",
+            source,
+        })?
+        .text()
+        .await
+        .map_err(|source| ApiError::Upstream {
+            context: "Could not read naughty list",
+            source,
+        })?;
+    let payload = credentials.decrypt(&encrypted)?;
+    let payload = String::from_utf8(payload)
+        .map_err(|e| ApiError::internal("Naughty list is not valid UTF-8", e))?;
+    let naughty_list = serde_json::from_str::<Vec<NaughtyItem>>(&payload)
+        .map_err(|e| ApiError::internal("Could not parse naughty list", e))?;
+    debug!(payload, "Decrypted naughty list");
     let mail_batches = naughty_list
         .iter()
         .map(|naughty| Response {
@@ -64,19 +201,32 @@ async fn get_naughty_list() {
     for chunks in mail_batches {
         let response = client
             .post("https://www.codehunt.rs/api/naughty")
-            .header("Authorization", "Bearer Lb7bB6PyL1kP0hU2")
+            .bearer_auth(&credentials.token)
+            .header(REQUEST_ID_HEADER, request_id.as_str())
             .json(&chunks)
             .send()
             .await
-            .unwrap();
-        println!("{:?}", response.status());
+            .map_err(|source| ApiError::Upstream {
+                context: "Could not send naughty mails",
+                source,
+            })?;
+        debug!(status = %response.status(), "Sent naughty mails");
     }
+    Ok(())
 }
 
 pub struct Tiebreaker {}
 
-impl Tiebreaker {
-    pub fn routes() -> Vec<Route> {
+impl Day for Tiebreaker {
+    const NAME: &'static str = "tiebreaker";
+    const PATH: &'static str = "/tiebreaker";
+    const DESCRIPTION: &'static str = "Decrypting and answering the naughty list";
+
+    fn routes() -> Vec<Route> {
         routes![get_naughty_list]
     }
+
+    fn manage(rocket: Rocket<Build>) -> Rocket<Build> {
+        rocket.attach(Credentials::fairing())
+    }
 }